@@ -1,4 +1,4 @@
-use std::{error::Error, fs};
+use std::{collections::BTreeMap, env, error::Error, fs};
 
 #[derive(Debug, Clone, PartialEq)]
 struct MapPoint {
@@ -15,14 +15,47 @@ struct Trailhead {
 
 #[derive(Debug, Clone)]
 struct TrailMap {
-    map: Vec<Vec<i8>>,
+    // None marks an impassable tile.
+    map: Vec<Vec<Option<u32>>>,
     trailheads: Vec<Trailhead>,
 }
 
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum ElevationFormat {
+    // One decimal digit per tile.
+    Digits,
+    // One hexadecimal digit per tile.
+    Hex,
+    // Whitespace separated numbers of any length.
+    Numbers,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct TrailRules {
+    // Height gains allowed in a single step along a trail, all positive.
+    steps: Vec<u32>,
+    // Trails may also step diagonally.
+    diagonal: bool,
+    start_height: u32,
+    end_height: u32,
+    elevations: ElevationFormat,
+}
+
+// Trails listed for a selected trailhead when no cap is given.
+const DEFAULT_MAX_TRAILS: usize = 10;
+
+// A trailhead whose individual trails should be listed, up to `max_trails` of them.
+#[derive(Debug, Clone)]
+struct TrailSelection {
+    trailhead: MapPoint,
+    max_trails: usize,
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let (trail_rules, trail_selection) = parse_arguments(env::args().skip(1))?;
     let map_source = fs::read_to_string("day-10/day10_input.txt")?;
-    let mut trail_map = parse_topographic_map(&map_source);
-    calculate_trailhead_scores(&mut trail_map);
+    let mut trail_map = parse_topographic_map(&map_source, &trail_rules);
+    let ratings = calculate_trailhead_scores(&mut trail_map, &trail_rules);
 
     let mut trailhead_scores_sum = 0;
     let mut trailhead_ratings_sum = 0;
@@ -34,114 +67,407 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("Sum of scores of all trailheads: {trailhead_scores_sum}");
     println!("Sum of ratings of all trailheads: {trailhead_ratings_sum}");
 
+    if let Some(trail_selection) = trail_selection {
+        let loc = &trail_selection.trailhead;
+        let trailhead = trail_map
+            .trailheads
+            .iter()
+            .find(|trailhead| trailhead.loc == *loc)
+            .ok_or(format!(
+                "There is no trailhead at row {}, col {}.",
+                loc.row, loc.col
+            ))?;
+        let trails = find_trails(
+            &trail_map,
+            &trail_rules,
+            &ratings,
+            loc,
+            trail_selection.max_trails,
+        );
+        println!(
+            "Listing {} of {} trails from the trailhead at row {}, col {}",
+            trails.len(),
+            trailhead.rating,
+            loc.row,
+            loc.col
+        );
+
+        let mut trail_renders = String::new();
+        for (trail_idx, trail) in trails.iter().enumerate() {
+            let route: Vec<String> = trail
+                .iter()
+                .map(|point| format!("({}, {})", point.row, point.col))
+                .collect();
+            trail_renders += &format!("Trail {}: {}\n", trail_idx + 1, route.join(" -> "));
+            trail_renders += &render_trail(&trail_map, &trail_rules, trail);
+            trail_renders.push('\n');
+        }
+        fs::write("day-10/trails.txt", trail_renders)?;
+        println!("Wrote trails to day-10/trails.txt");
+    }
+
     Ok(())
 }
 
-fn parse_topographic_map(topographic_map: &str) -> TrailMap {
+fn parse_arguments(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(TrailRules, Option<TrailSelection>), Box<dyn Error>> {
+    let mut trail_rules = TrailRules {
+        steps: vec![1],
+        diagonal: false,
+        start_height: 0,
+        end_height: 9,
+        elevations: ElevationFormat::Digits,
+    };
+
+    let mut trailhead = None;
+    let mut max_trails = DEFAULT_MAX_TRAILS;
+
+    let parse_height = |flag: &str, value: Option<String>| -> Result<u32, String> {
+        value
+            .as_deref()
+            .and_then(|value| value.parse().ok())
+            .ok_or(format!("Expected a height after {flag}, found {value:?}."))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--steps" => {
+                let steps = args
+                    .next()
+                    .ok_or("Expected a list of steps after --steps.")?;
+                trail_rules.steps = steps
+                    .split(',')
+                    .map(|step| match step.trim_start_matches('+').parse() {
+                        Ok(step) if step > 0 => Ok(step),
+                        _ => Err(format!("Steps must be positive integers, found '{step}'.")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--diagonal" => trail_rules.diagonal = true,
+            "--start" => trail_rules.start_height = parse_height("--start", args.next())?,
+            "--end" => trail_rules.end_height = parse_height("--end", args.next())?,
+            "--elevations" => {
+                trail_rules.elevations = match args.next().as_deref() {
+                    Some("digits") => ElevationFormat::Digits,
+                    Some("hex") => ElevationFormat::Hex,
+                    Some("numbers") => ElevationFormat::Numbers,
+                    other => {
+                        return Err(format!(
+                    "Expected 'digits', 'hex' or 'numbers' after --elevations, found {other:?}."
+                )
+                        .into())
+                    }
+                }
+            }
+            "--trailhead" => {
+                let loc = args.next().unwrap_or_default();
+                trailhead = match loc
+                    .split_once(',')
+                    .map(|(row, col)| (row.parse(), col.parse()))
+                {
+                    Some((Ok(row), Ok(col))) => Some(MapPoint { row, col }),
+                    _ => {
+                        return Err(format!(
+                            "Expected a ROW,COL location after --trailhead, found '{loc}'."
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--max-trails" => {
+                max_trails = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("Expected a number after --max-trails.")?;
+            }
+            _ => return Err(format!("Unknown argument '{arg}'.").into()),
+        }
+    }
+    if trail_rules.start_height > trail_rules.end_height {
+        return Err("The start height must not be above the end height.".into());
+    }
+
+    let trail_selection = trailhead.map(|trailhead| TrailSelection {
+        trailhead,
+        max_trails,
+    });
+
+    Ok((trail_rules, trail_selection))
+}
+
+fn parse_topographic_map(topographic_map: &str, trail_rules: &TrailRules) -> TrailMap {
     let mut trail_map = TrailMap {
         map: Vec::new(),
         trailheads: Vec::new(),
     };
 
-    let mut row_idx = 0;
-    let mut col_idx = 0;
-
-    let mut current_row = Vec::new();
-    for c in topographic_map.chars() {
-        match c {
-            '\n' => {
-                row_idx += 1;
-                col_idx = 0;
-                trail_map.map.push(current_row);
-                current_row = Vec::new();
-            }
-            '0' => {
-                current_row.push(0);
-                let loc = MapPoint {
-                    row: row_idx,
-                    col: col_idx,
-                };
-                trail_map.trailheads.push(Trailhead {
-                    loc,
-                    peaks: Vec::new(),
-                    rating: 0,
-                });
-                col_idx += 1;
-            }
-            _ => {
-                current_row.push(c.to_digit(10).unwrap() as i8);
-                col_idx += 1;
+    for (row_idx, line) in topographic_map.lines().enumerate() {
+        let tiles: Vec<&str> = match trail_rules.elevations {
+            ElevationFormat::Digits | ElevationFormat::Hex => line
+                .char_indices()
+                .map(|(idx, c)| &line[idx..idx + c.len_utf8()])
+                .collect(),
+            ElevationFormat::Numbers => line.split_whitespace().collect(),
+        };
+        let radix = match trail_rules.elevations {
+            ElevationFormat::Hex => 16,
+            ElevationFormat::Digits | ElevationFormat::Numbers => 10,
+        };
+
+        let mut current_row = Vec::new();
+        for (col_idx, tile) in tiles.into_iter().enumerate() {
+            match tile {
+                // Impassable tile.
+                "." => current_row.push(None),
+                _ => {
+                    let height = u32::from_str_radix(tile, radix)
+                        .unwrap_or_else(|_| panic!("Failed to parse {tile} to a height."));
+                    if height == trail_rules.start_height {
+                        trail_map.trailheads.push(Trailhead {
+                            loc: MapPoint {
+                                row: row_idx,
+                                col: col_idx,
+                            },
+                            peaks: Vec::new(),
+                            rating: 0,
+                        });
+                    }
+                    current_row.push(Some(height));
+                }
             }
         }
+        trail_map.map.push(current_row);
     }
 
     trail_map
 }
 
-fn calculate_trailhead_scores(trail_map: &mut TrailMap) {
-    for trailhead in trail_map.trailheads.iter_mut() {
-        trailhead.rating =
-            find_trailhead_peaks(&trailhead.loc, &trail_map.map, &mut trailhead.peaks);
+// Works down from the peaks one height at a time, so every tile's rating (the number of trails
+// from it to a peak) and the set of peaks it reaches are computed once from the tiles above it.
+// Reachable peaks are kept as bitsets indexed by peak, and only for heights within one step.
+// Returns the rating of every tile, indexed by row * num_cols + col.
+fn calculate_trailhead_scores(trail_map: &mut TrailMap, trail_rules: &TrailRules) -> Vec<usize> {
+    let num_rows = trail_map.map.len();
+    let num_cols = trail_map.map.first().map_or(0, |row| row.len());
+    let max_step = trail_rules.steps.iter().copied().max().unwrap_or(0);
+
+    let mut tiles_by_height = BTreeMap::new();
+    for (row_idx, row) in trail_map.map.iter().enumerate() {
+        for (col_idx, height) in row.iter().enumerate() {
+            match height {
+                Some(height)
+                    if (trail_rules.start_height..=trail_rules.end_height).contains(height) =>
+                {
+                    tiles_by_height
+                        .entry(*height)
+                        .or_insert_with(Vec::new)
+                        .push(row_idx * num_cols + col_idx);
+                }
+                _ => (),
+            }
+        }
     }
-}
+    let levels: Vec<(u32, Vec<usize>)> = tiles_by_height.into_iter().rev().collect();
 
-fn find_trailhead_peaks(loc: &MapPoint, map: &Vec<Vec<i8>>, peaks: &mut Vec<MapPoint>) -> usize {
-    let cur_height = map[loc.row][loc.col];
+    let peaks: &[usize] = match levels.first() {
+        Some((height, tiles)) if *height == trail_rules.end_height => tiles,
+        _ => &[],
+    };
+    let bitset_len = peaks.len().div_ceil(64);
+    let mut ratings: Vec<usize> = vec![0; num_rows * num_cols];
+    let mut reachable_peaks: Vec<Vec<u64>> = vec![Vec::new(); num_rows * num_cols];
+    for (peak_idx, &tile_idx) in peaks.iter().enumerate() {
+        ratings[tile_idx] = 1;
+        reachable_peaks[tile_idx] = vec![0; bitset_len];
+        reachable_peaks[tile_idx][peak_idx / 64] |= 1 << (peak_idx % 64);
+    }
 
-    if map[loc.row][loc.col] == 9 {
-        if !peaks.contains(loc) {
-            peaks.push(loc.clone());
+    let mut next_level_to_free = 0;
+    for (height, tiles) in levels
+        .iter()
+        .filter(|(height, _)| *height != trail_rules.end_height)
+    {
+        for &tile_idx in tiles.iter() {
+            let mut reachable = vec![0; bitset_len];
+            for next_idx in neighbours(tile_idx, num_rows, num_cols, trail_rules.diagonal) {
+                let next_height = trail_map.map[next_idx / num_cols][next_idx % num_cols];
+                match next_height {
+                    Some(next_height)
+                        if next_height > *height
+                            && trail_rules.steps.contains(&(next_height - height)) =>
+                    {
+                        ratings[tile_idx] = ratings[tile_idx]
+                            .checked_add(ratings[next_idx])
+                            .expect("Trail rating overflowed.");
+                        for (word, next_word) in
+                            reachable.iter_mut().zip(&reachable_peaks[next_idx])
+                        {
+                            *word |= next_word;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            reachable_peaks[tile_idx] = reachable;
+        }
+        // Tiles more than a step higher are no longer needed.
+        while levels[next_level_to_free].0 > height.saturating_add(max_step) {
+            for &tile_idx in levels[next_level_to_free].1.iter() {
+                reachable_peaks[tile_idx] = Vec::new();
+            }
+            next_level_to_free += 1;
         }
-        return 1;
     }
-    // Search Up
-    let mut score_up = 0;
-    if loc.row > 0 && map[loc.row - 1][loc.col] - cur_height == 1 {
-        score_up = find_trailhead_peaks(
-            &MapPoint {
-                row: loc.row - 1,
-                col: loc.col,
-            },
-            map,
-            peaks,
-        );
+
+    for trailhead in trail_map.trailheads.iter_mut() {
+        let tile_idx = trailhead.loc.row * num_cols + trailhead.loc.col;
+        trailhead.rating = ratings[tile_idx];
+        trailhead.peaks = peaks
+            .iter()
+            .enumerate()
+            .filter(|(peak_idx, _)| {
+                reachable_peaks[tile_idx][peak_idx / 64] >> (peak_idx % 64) & 1 == 1
+            })
+            .map(|(_, &peak_tile_idx)| MapPoint {
+                row: peak_tile_idx / num_cols,
+                col: peak_tile_idx % num_cols,
+            })
+            .collect();
     }
-    // Search Right
-    let mut score_right = 0;
-    if loc.col + 1 < map[0].len() && map[loc.row][loc.col + 1] - cur_height == 1 {
-        score_right = find_trailhead_peaks(
-            &MapPoint {
-                row: loc.row,
-                col: loc.col + 1,
-            },
-            map,
-            peaks,
-        );
+
+    ratings
+}
+
+// Lists up to `max_trails` distinct trails from `trailhead` to a peak. Only tiles with a non-zero
+// rating are followed, so no time is spent on dead ends.
+fn find_trails(
+    trail_map: &TrailMap,
+    trail_rules: &TrailRules,
+    ratings: &[usize],
+    trailhead: &MapPoint,
+    max_trails: usize,
+) -> Vec<Vec<MapPoint>> {
+    let num_cols = trail_map.map.first().map_or(0, |row| row.len());
+    let mut trails = Vec::new();
+    let mut trail = vec![trailhead.row * num_cols + trailhead.col];
+    extend_trail(
+        trail_map,
+        trail_rules,
+        ratings,
+        &mut trail,
+        &mut trails,
+        max_trails,
+    );
+
+    trails
+        .into_iter()
+        .map(|trail| {
+            trail
+                .into_iter()
+                .map(|tile_idx| MapPoint {
+                    row: tile_idx / num_cols,
+                    col: tile_idx % num_cols,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn extend_trail(
+    trail_map: &TrailMap,
+    trail_rules: &TrailRules,
+    ratings: &[usize],
+    trail: &mut Vec<usize>,
+    trails: &mut Vec<Vec<usize>>,
+    max_trails: usize,
+) {
+    let num_rows = trail_map.map.len();
+    let num_cols = trail_map.map.first().map_or(0, |row| row.len());
+    let tile_idx = trail[trail.len() - 1];
+    let height = trail_map.map[tile_idx / num_cols][tile_idx % num_cols];
+
+    if trails.len() == max_trails || ratings[tile_idx] == 0 {
+        return;
     }
-    // Search Down
-    let mut score_down = 0;
-    if loc.row + 1 < map.len() && map[loc.row + 1][loc.col] - cur_height == 1 {
-        score_down = find_trailhead_peaks(
-            &MapPoint {
-                row: loc.row + 1,
-                col: loc.col,
-            },
-            map,
-            peaks,
-        );
+    if height == Some(trail_rules.end_height) {
+        trails.push(trail.clone());
+        return;
     }
-    // Search Left
-    let mut score_left = 0;
-    if loc.col > 0 && map[loc.row][loc.col - 1] - cur_height == 1 {
-        score_left = find_trailhead_peaks(
-            &MapPoint {
-                row: loc.row,
-                col: loc.col - 1,
-            },
-            map,
-            peaks,
-        );
+    for next_idx in neighbours(tile_idx, num_rows, num_cols, trail_rules.diagonal) {
+        let next_height = trail_map.map[next_idx / num_cols][next_idx % num_cols];
+        let is_step = match (height, next_height) {
+            (Some(height), Some(next_height)) => {
+                next_height > height && trail_rules.steps.contains(&(next_height - height))
+            }
+            _ => false,
+        };
+        if is_step {
+            trail.push(next_idx);
+            extend_trail(trail_map, trail_rules, ratings, trail, trails, max_trails);
+            trail.pop();
+        }
+    }
+}
+
+// Draws the map with only the tiles along the trail showing their heights.
+fn render_trail(trail_map: &TrailMap, trail_rules: &TrailRules, trail: &[MapPoint]) -> String {
+    let mut render = String::new();
+    for (row_idx, row) in trail_map.map.iter().enumerate() {
+        let tiles: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(col_idx, height)| {
+                let on_trail = trail
+                    .iter()
+                    .any(|point| point.row == row_idx && point.col == col_idx);
+                match (height, on_trail) {
+                    (Some(height), true) => match trail_rules.elevations {
+                        ElevationFormat::Digits | ElevationFormat::Numbers => height.to_string(),
+                        ElevationFormat::Hex => format!("{height:x}"),
+                    },
+                    _ => ".".to_string(),
+                }
+            })
+            .collect();
+        match trail_rules.elevations {
+            ElevationFormat::Digits | ElevationFormat::Hex => render += &tiles.concat(),
+            ElevationFormat::Numbers => render += &tiles.join(" "),
+        }
+        render.push('\n');
     }
 
-    score_up + score_right + score_down + score_left
+    render
+}
+
+// Indices of the tiles around a tile that are on the map, clockwise from up and including the
+// diagonal ones if asked for.
+fn neighbours(
+    tile_idx: usize,
+    num_rows: usize,
+    num_cols: usize,
+    diagonal: bool,
+) -> impl Iterator<Item = usize> {
+    let (row, col) = (tile_idx / num_cols, tile_idx % num_cols);
+    let offsets: &[(isize, isize)] = match diagonal {
+        true => &[
+            (-1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+        ],
+        false => &[(-1, 0), (0, 1), (1, 0), (0, -1)],
+    };
+    offsets.iter().filter_map(move |(row_offset, col_offset)| {
+        let next_row = row
+            .checked_add_signed(*row_offset)
+            .filter(|&row| row < num_rows)?;
+        let next_col = col
+            .checked_add_signed(*col_offset)
+            .filter(|&col| col < num_cols)?;
+        Some(next_row * num_cols + next_col)
+    })
 }
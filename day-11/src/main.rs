@@ -1,22 +1,153 @@
-use std::{collections::HashMap, error::Error, fs};
+use std::{collections::HashMap, env, error::Error, fmt, fs};
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum CountWidth {
+    Usize,
+    U128,
+    Big,
+}
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum StonePredicate {
+    Always,
+    Equals(usize),
+    EvenDigits,
+    DivisibleBy(usize),
+}
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum StoneAction {
+    Replace(usize),
+    // The right stone gets the lower half of the digits, the left stone the rest.
+    Split,
+    Multiply(usize),
+    Add(usize),
+}
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+struct StoneRule {
+    predicate: StonePredicate,
+    action: StoneAction,
+}
+
+// The rules from the puzzle, in the order they are checked.
+const PUZZLE_RULES: [StoneRule; 3] = [
+    StoneRule {
+        predicate: StonePredicate::Equals(0),
+        action: StoneAction::Replace(1),
+    },
+    StoneRule {
+        predicate: StonePredicate::EvenDigits,
+        action: StoneAction::Split,
+    },
+    StoneRule {
+        predicate: StonePredicate::Always,
+        action: StoneAction::Multiply(2024),
+    },
+];
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut total_blinks = Vec::new();
+    let mut num_distribution_blinks = 75;
+    let mut export_distribution = false;
+    let mut rules = PUZZLE_RULES.to_vec();
+    let mut count_width = CountWidth::Usize;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--total-blinks" => {
+                total_blinks.push(
+                    args.next()
+                        .and_then(|value| value.parse().ok())
+                        .ok_or("Expected a number after --total-blinks.")?,
+                );
+            }
+            "--blinks" => {
+                num_distribution_blinks = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or("Expected a number after --blinks.")?;
+            }
+            "--rules" => {
+                let rules_path = args.next().ok_or("Expected a file path after --rules.")?;
+                rules = parse_stone_rules(&fs::read_to_string(&rules_path)?)?;
+            }
+            "--counts" => {
+                count_width = match args.next().as_deref() {
+                    Some("usize") => CountWidth::Usize,
+                    Some("u128") => CountWidth::U128,
+                    Some("big") => CountWidth::Big,
+                    other => {
+                        return Err(format!(
+                            "Expected 'usize', 'u128' or 'big' after --counts, found {other:?}."
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--export" => export_distribution = true,
+            _ => return Err(format!("Unknown argument '{arg}'.").into()),
+        }
+    }
+
+    if total_blinks.is_empty() {
+        total_blinks = vec![25, 75];
+    }
+
     let stone_line = fs::read_to_string("day-11/day11_input.txt")?;
     let stones = parse_stone_line(&stone_line);
 
-    let mut blink_cache = HashMap::new();
+    // The distribution lists every stone value after each blink, so it is only reported when
+    // asked for.
+    let num_distribution_blinks = export_distribution.then_some(num_distribution_blinks);
+    match count_width {
+        CountWidth::Usize => {
+            run_blinks::<usize>(&stones, &rules, &total_blinks, num_distribution_blinks)
+        }
+        CountWidth::U128 => {
+            run_blinks::<u128>(&stones, &rules, &total_blinks, num_distribution_blinks)
+        }
+        CountWidth::Big => {
+            run_blinks::<BigCount>(&stones, &rules, &total_blinks, num_distribution_blinks)
+        }
+    }
+}
 
-    let mut total_after_25_blinks = 0;
-    for stone in stones.iter() {
-        total_after_25_blinks += blink(*stone, 25, &mut blink_cache);
+fn run_blinks<C: StoneCount>(
+    stones: &[usize],
+    rules: &[StoneRule],
+    total_blinks: &[usize],
+    num_distribution_blinks: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    for &num_blinks in total_blinks {
+        let total_stones = blink::<C>(stones, num_blinks, rules)?;
+        println!("{total_stones} stones after {num_blinks} blinks.");
     }
-    println!("{total_after_25_blinks} stones after 25 blinks.");
 
-    let mut total_after_75_blinks = 0;
-    for stone in stones.iter() {
-        total_after_75_blinks += blink(*stone, 75, &mut blink_cache);
+    let Some(num_distribution_blinks) = num_distribution_blinks else {
+        return Ok(());
+    };
+    let mut stone_counts = count_stone_values(stones)?;
+    let mut distribution = String::new();
+    for blink_idx in 1..=num_distribution_blinks {
+        stone_counts = blink_all(&stone_counts, rules)?;
+
+        let total_stones = total_stone_count(&stone_counts)?;
+        let largest_value = stone_counts.keys().max().unwrap_or(&0);
+        println!(
+            "Blink {blink_idx}: {total_stones} stones, {} distinct values, largest value {largest_value}",
+            stone_counts.len()
+        );
+
+        let mut values: Vec<(&usize, &C)> = stone_counts.iter().collect();
+        values.sort_unstable_by_key(|(value, _)| **value);
+        distribution += &format!("Blink {blink_idx}\n");
+        for (value, count) in values {
+            distribution += &format!("{value} {count}\n");
+        }
     }
-    println!("{total_after_75_blinks} stones after 75 blinks.");
+    fs::write("day-11/stone_distribution.txt", distribution)?;
+    println!("Wrote stone value distributions to day-11/stone_distribution.txt");
 
     Ok(())
 }
@@ -41,41 +172,252 @@ fn parse_stone_line(stone_line: &str) -> Vec<usize> {
     stones
 }
 
-fn blink(
-    stone_num: usize,
-    num_blinks: usize,
-    blink_cache: &mut HashMap<(usize, usize), usize>,
-) -> usize {
-    let cache_key = (stone_num, num_blinks);
-    if let Some(&num_stones) = blink_cache.get(&cache_key) {
-        return num_stones;
-    }
-
-    let num_stones = match num_blinks {
-        1 => {
-            if stone_num != 0 && (stone_num.ilog10() + 1) & 1 == 0 {
-                2
-            } else {
-                1
-            }
+// Parses one rule per line in the form `<predicate> -> <action>`, e.g. `even-digits -> split`.
+// Predicates are `always`, `equals N`, `even-digits` and `divisible-by N`, actions are
+// `replace N`, `split`, `multiply N` and `add N`. Blank lines and lines starting with '#' are
+// skipped. The first rule whose predicate matches a stone is applied, stones matching no rule
+// are left as they are.
+fn parse_stone_rules(source: &str) -> Result<Vec<StoneRule>, String> {
+    let mut rules = Vec::new();
+    for (line_idx, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
-        _ => match stone_num {
-            0 => blink(1, num_blinks - 1, blink_cache),
-            _ if (stone_num.ilog10() + 1) & 1 == 0 => {
-                let num_digits = stone_num.ilog10() + 1;
-                let left_half = stone_num / 10usize.pow(num_digits / 2);
-                let left_res = blink(left_half, num_blinks - 1, blink_cache);
 
-                let right_half = stone_num % 10usize.pow(num_digits / 2);
-                let right_res = blink(right_half, num_blinks - 1, blink_cache);
+        let invalid_rule = || format!("Invalid rule '{line}' on line {}.", line_idx + 1);
+        let (predicate, action) = line.split_once("->").ok_or_else(invalid_rule)?;
+        let predicate: Vec<&str> = predicate.split_whitespace().collect();
+        let action: Vec<&str> = action.split_whitespace().collect();
+        let parse_number = |value: &str| value.parse().map_err(|_| invalid_rule());
 
-                left_res + right_res
-            }
-            _ => blink(stone_num * 2024, num_blinks - 1, blink_cache),
-        },
+        let predicate = match predicate[..] {
+            ["always"] => StonePredicate::Always,
+            ["equals", value] => StonePredicate::Equals(parse_number(value)?),
+            ["even-digits"] => StonePredicate::EvenDigits,
+            ["divisible-by", value] => match parse_number(value)? {
+                0 => return Err(invalid_rule()),
+                divisor => StonePredicate::DivisibleBy(divisor),
+            },
+            _ => return Err(invalid_rule()),
+        };
+        let action = match action[..] {
+            ["replace", value] => StoneAction::Replace(parse_number(value)?),
+            ["split"] => StoneAction::Split,
+            ["multiply", value] => StoneAction::Multiply(parse_number(value)?),
+            ["add", value] => StoneAction::Add(parse_number(value)?),
+            _ => return Err(invalid_rule()),
+        };
+        rules.push(StoneRule { predicate, action });
+    }
+
+    Ok(rules)
+}
+
+// The stones a single stone turns into on one blink.
+fn apply_stone_rules(stone_num: usize, rules: &[StoneRule]) -> Result<Vec<usize>, String> {
+    let num_digits = match stone_num {
+        0 => 1,
+        _ => stone_num.ilog10() + 1,
+    };
+    let rule = rules.iter().find(|rule| match rule.predicate {
+        StonePredicate::Always => true,
+        StonePredicate::Equals(value) => stone_num == value,
+        StonePredicate::EvenDigits => num_digits & 1 == 0,
+        StonePredicate::DivisibleBy(divisor) => stone_num.is_multiple_of(divisor),
+    });
+
+    let next_stones = match rule.map(|rule| rule.action) {
+        None => Some(vec![stone_num]),
+        Some(StoneAction::Replace(value)) => Some(vec![value]),
+        Some(StoneAction::Split) => Some(vec![
+            stone_num / 10usize.pow(num_digits / 2),
+            stone_num % 10usize.pow(num_digits / 2),
+        ]),
+        Some(StoneAction::Multiply(factor)) => {
+            stone_num.checked_mul(factor).map(|value| vec![value])
+        }
+        Some(StoneAction::Add(value)) => stone_num.checked_add(value).map(|value| vec![value]),
     };
 
-    blink_cache.insert(cache_key, num_stones);
+    next_stones.ok_or(format!(
+        "Stone value {stone_num} overflowed applying {:?}, stone values are limited to {} bits.",
+        rule.map(|rule| rule.action),
+        usize::BITS
+    ))
+}
+
+// Counts the stones after `num_blinks` by evolving how many stones carry each value one blink at
+// a time. Stones sharing a value are only transformed once, and nothing recurses on the number of
+// blinks.
+fn blink<C: StoneCount>(
+    stones: &[usize],
+    num_blinks: usize,
+    rules: &[StoneRule],
+) -> Result<C, String> {
+    let mut stone_counts = count_stone_values(stones)?;
+    for _ in 0..num_blinks {
+        stone_counts = blink_all(&stone_counts, rules)?;
+    }
+
+    total_stone_count(&stone_counts)
+}
+
+fn count_stone_values<C: StoneCount>(stones: &[usize]) -> Result<HashMap<usize, C>, String> {
+    let mut stone_counts = HashMap::new();
+    for stone in stones {
+        stone_counts
+            .entry(*stone)
+            .or_insert_with(C::zero)
+            .try_add_assign(&C::one())
+            .ok_or_else(C::overflow_error)?;
+    }
+
+    Ok(stone_counts)
+}
+
+fn total_stone_count<C: StoneCount>(stone_counts: &HashMap<usize, C>) -> Result<C, String> {
+    let mut total = C::zero();
+    for count in stone_counts.values() {
+        total.try_add_assign(count).ok_or_else(C::overflow_error)?;
+    }
+
+    Ok(total)
+}
+
+// Evolves a whole generation of stones at once, tracking how many stones carry each value rather
+// than each stone on its own.
+fn blink_all<C: StoneCount>(
+    stone_counts: &HashMap<usize, C>,
+    rules: &[StoneRule],
+) -> Result<HashMap<usize, C>, String> {
+    let mut next_stone_counts = HashMap::new();
+    for (&stone_num, count) in stone_counts.iter() {
+        for next_stone in apply_stone_rules(stone_num, rules)? {
+            next_stone_counts
+                .entry(next_stone)
+                .or_insert_with(C::zero)
+                .try_add_assign(count)
+                .ok_or_else(C::overflow_error)?;
+        }
+    }
+
+    Ok(next_stone_counts)
+}
+
+// Stone counts grow exponentially with the number of blinks, so they can be kept in wider
+// integers than the stone values themselves.
+trait StoneCount: Clone + fmt::Display {
+    fn zero() -> Self;
+    fn one() -> Self;
+    // Returns None if the sum doesn't fit.
+    fn try_add_assign(&mut self, other: &Self) -> Option<()>;
+    fn overflow_error() -> String;
+}
+
+impl StoneCount for usize {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
 
-    num_stones
+    fn try_add_assign(&mut self, other: &Self) -> Option<()> {
+        *self = self.checked_add(*other)?;
+        Some(())
+    }
+
+    fn overflow_error() -> String {
+        "Stone count overflowed 64 bits, try --counts u128 or --counts big.".to_string()
+    }
+}
+
+impl StoneCount for u128 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn try_add_assign(&mut self, other: &Self) -> Option<()> {
+        *self = self.checked_add(*other)?;
+        Some(())
+    }
+
+    fn overflow_error() -> String {
+        "Stone count overflowed 128 bits, try --counts big.".to_string()
+    }
+}
+
+// Arbitrary precision unsigned integer, only supporting what stone counting needs. Stored as
+// little-endian base 2^32 limbs without trailing zero limbs.
+#[derive(Clone, PartialEq, Debug)]
+struct BigCount {
+    limbs: Vec<u32>,
+}
+
+impl StoneCount for BigCount {
+    fn zero() -> Self {
+        BigCount { limbs: Vec::new() }
+    }
+
+    fn one() -> Self {
+        BigCount { limbs: vec![1] }
+    }
+
+    fn try_add_assign(&mut self, other: &Self) -> Option<()> {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = 0;
+        for (idx, limb) in self.limbs.iter_mut().enumerate() {
+            let sum = *limb as u64 + *other.limbs.get(idx).unwrap_or(&0) as u64 + carry;
+            *limb = sum as u32;
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+
+        Some(())
+    }
+
+    fn overflow_error() -> String {
+        unreachable!("Arbitrary precision counts can't overflow.")
+    }
+}
+
+impl fmt::Display for BigCount {
+    // Repeatedly divides by 10^9 to print nine decimal digits at a time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut limbs = self.limbs.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder = 0;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 32) | *limb as u64;
+                *limb = (value / 1_000_000_000) as u32;
+                remainder = value % 1_000_000_000;
+            }
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+            chunks.push(remainder);
+        }
+
+        match chunks.pop() {
+            None => write!(f, "0"),
+            Some(first_chunk) => {
+                write!(f, "{first_chunk}")?;
+                for chunk in chunks.iter().rev() {
+                    write!(f, "{chunk:09}")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
@@ -1,6 +1,15 @@
-use std::{collections::HashSet, error::Error, fs};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+    iter::Peekable,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// Size of a plot in the SVG drawing of the garden.
+const SVG_PLOT_SIZE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct MapPoint {
     row: usize,
     col: usize,
@@ -16,13 +25,15 @@ enum Direction {
 
 #[derive(Debug, Clone)]
 struct Region {
+    plant: char,
     area: usize,
     perimeter: usize,
     fence_sides: usize,
 }
 impl Region {
-    fn new() -> Self {
+    fn new(plant: char) -> Self {
         Region {
+            plant,
             area: 0,
             perimeter: 0,
             fence_sides: 0,
@@ -30,115 +41,697 @@ impl Region {
     }
 }
 
+// Every plot labelled with the index of its region in `regions`, stored row by row.
+#[derive(Debug, Clone)]
+struct GardenRegions {
+    num_cols: usize,
+    labels: Vec<usize>,
+    regions: Vec<Region>,
+}
+
+// Fence lines around a region as closed polygons. Vertices are plot corners, so a plot at
+// (row, col) spans the corners (row, col) to (row + 1, col + 1), and only corners where the fence
+// turns are listed. The outer boundary runs clockwise on the map and holes run anticlockwise.
+#[derive(Debug, Clone)]
+struct RegionPolygons {
+    outer: Vec<MapPoint>,
+    holes: Vec<Vec<MapPoint>>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum RegionMetric {
+    Area,
+    Perimeter,
+    Sides,
+    // Distinct fence corners, where fence posts go. Fences meeting diagonally share a corner.
+    Corners,
+    // Areas of other plots surrounded by the region on every side, even if they touch the
+    // outside at a corner.
+    Holes,
+    // Price per unit for the region's plant type.
+    UnitPrice,
+}
+
+// A price formula over region metrics, e.g. `unit_price * (area * sides + 10 * corners)`.
+#[derive(Clone, PartialEq, Debug)]
+enum PriceFormula {
+    Constant(f64),
+    Metric(RegionMetric),
+    Add(Box<PriceFormula>, Box<PriceFormula>),
+    Subtract(Box<PriceFormula>, Box<PriceFormula>),
+    Multiply(Box<PriceFormula>, Box<PriceFormula>),
+    Divide(Box<PriceFormula>, Box<PriceFormula>),
+}
+
+impl PriceFormula {
+    fn price(&self, region: &Region, polygons: &RegionPolygons, unit_prices: &UnitPrices) -> f64 {
+        match self {
+            PriceFormula::Constant(value) => *value,
+            PriceFormula::Metric(metric) => match metric {
+                RegionMetric::Area => region.area as f64,
+                RegionMetric::Perimeter => region.perimeter as f64,
+                RegionMetric::Sides => region.fence_sides as f64,
+                RegionMetric::Corners => {
+                    let corners: HashSet<&MapPoint> = polygons
+                        .holes
+                        .iter()
+                        .chain([&polygons.outer])
+                        .flatten()
+                        .collect();
+                    corners.len() as f64
+                }
+                RegionMetric::Holes => polygons.holes.len() as f64,
+                RegionMetric::UnitPrice => unit_prices.price_of(region.plant),
+            },
+            PriceFormula::Add(lhs, rhs) => {
+                lhs.price(region, polygons, unit_prices) + rhs.price(region, polygons, unit_prices)
+            }
+            PriceFormula::Subtract(lhs, rhs) => {
+                lhs.price(region, polygons, unit_prices) - rhs.price(region, polygons, unit_prices)
+            }
+            PriceFormula::Multiply(lhs, rhs) => {
+                lhs.price(region, polygons, unit_prices) * rhs.price(region, polygons, unit_prices)
+            }
+            PriceFormula::Divide(lhs, rhs) => {
+                lhs.price(region, polygons, unit_prices) / rhs.price(region, polygons, unit_prices)
+            }
+        }
+    }
+}
+
+// Unit prices per plant type, with plants that aren't listed costing one per unit.
+#[derive(Clone, PartialEq, Debug)]
+struct UnitPrices {
+    by_plant: HashMap<char, f64>,
+}
+
+impl UnitPrices {
+    fn price_of(&self, plant: char) -> f64 {
+        *self.by_plant.get(&plant).unwrap_or(&1.0)
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut price_formulas = Vec::new();
+    let mut unit_prices = UnitPrices {
+        by_plant: HashMap::new(),
+    };
+    let mut export_regions = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--price" => {
+                let formula = args
+                    .next()
+                    .ok_or("Expected a price formula after --price.")?;
+                price_formulas.push((formula.clone(), parse_price_formula(&formula)?));
+            }
+            "--unit-prices" => {
+                let path = args
+                    .next()
+                    .ok_or("Expected a file path after --unit-prices.")?;
+                unit_prices = parse_unit_prices(&fs::read_to_string(&path)?)?;
+            }
+            "--export" => export_regions = true,
+            _ => return Err(format!("Unknown argument '{arg}'.").into()),
+        }
+    }
+
     let garden_plots: Vec<Vec<char>> = fs::read_to_string("day-12/day12_input.txt")?
         .trim()
         .split('\n')
         .map(|r| r.chars().collect())
         .collect();
 
-    let (price_of_fencing, bulk_discount_price) = calculate_fencing_price(&garden_plots);
+    let garden_regions = label_regions(&garden_plots);
+    let region_polygons = trace_region_polygons(&garden_regions);
+    let price_of_fencing = calculate_fencing_price(
+        &garden_regions,
+        &region_polygons,
+        &parse_price_formula("standard")?,
+        &unit_prices,
+    );
+    let bulk_discount_price = calculate_fencing_price(
+        &garden_regions,
+        &region_polygons,
+        &parse_price_formula("bulk")?,
+        &unit_prices,
+    );
     println!("Total price of fencing all regions: {price_of_fencing}");
     println!("Total price of fencing all regions with bulk discount: {bulk_discount_price}");
+    for (formula_source, price_formula) in price_formulas.iter() {
+        let price = calculate_fencing_price(
+            &garden_regions,
+            &region_polygons,
+            price_formula,
+            &unit_prices,
+        );
+        println!("Total price of fencing all regions priced as '{formula_source}': {price}");
+    }
+
+    // Region details are written to files, so only when asked for.
+    if export_regions {
+        let mut label_grid = String::new();
+        for row in garden_regions.labels.chunks(garden_regions.num_cols) {
+            let row: Vec<String> = row.iter().map(usize::to_string).collect();
+            label_grid += &row.join(" ");
+            label_grid.push('\n');
+        }
+        fs::write("day-12/region_labels.txt", label_grid)?;
+        println!(
+            "Wrote labels of {} regions to day-12/region_labels.txt",
+            garden_regions.regions.len()
+        );
+
+        fs::write(
+            "day-12/region_polygons.json",
+            region_polygons_to_json(&garden_regions, &region_polygons),
+        )?;
+        println!("Wrote region fence polygons to day-12/region_polygons.json");
+        fs::write(
+            "day-12/garden.svg",
+            render_garden_svg(&garden_regions, &region_polygons),
+        )?;
+        println!("Wrote garden drawing to day-12/garden.svg");
+
+        let enclosing_regions = find_enclosing_regions(&garden_regions, &region_polygons);
+        let mut region_report =
+            String::from("Label | Plant | Area | Perimeter | Sides | Holes | Enclosed by\n");
+        for (label, region) in garden_regions.regions.iter().enumerate() {
+            let enclosed_by = match enclosing_regions[label] {
+                Some(enclosing_label) => format!(
+                    "{enclosing_label} ({})",
+                    garden_regions.regions[enclosing_label].plant
+                ),
+                None => "-".to_string(),
+            };
+            region_report += &format!(
+                "{label} | {} | {} | {} | {} | {} | {enclosed_by}\n",
+                region.plant,
+                region.area,
+                region.perimeter,
+                region.fence_sides,
+                region_polygons[label].holes.len()
+            );
+        }
+        fs::write("day-12/region_report.txt", region_report)?;
+        println!(
+            "{} regions have holes and {} regions are enclosed by another, wrote region report to day-12/region_report.txt",
+            region_polygons
+                .iter()
+                .filter(|polygons| !polygons.holes.is_empty())
+                .count(),
+            enclosing_regions.iter().flatten().count()
+        );
+    }
 
     Ok(())
 }
 
-fn calculate_fencing_price(garden_plots: &Vec<Vec<char>>) -> (usize, usize) {
-    let mut visited_set = HashSet::new();
-    let mut price = 0;
-    let mut discount_price = 0;
+fn calculate_fencing_price(
+    garden_regions: &GardenRegions,
+    region_polygons: &[RegionPolygons],
+    price_formula: &PriceFormula,
+    unit_prices: &UnitPrices,
+) -> f64 {
+    garden_regions
+        .regions
+        .iter()
+        .zip(region_polygons)
+        .map(|(region, polygons)| price_formula.price(region, polygons, unit_prices))
+        .sum()
+}
 
-    for row in 0..garden_plots.len() {
-        for col in 0..garden_plots[row].len() {
-            let loc = MapPoint { row, col };
-            if !visited_set.contains(&loc) {
-                let mut region = Region::new();
-                traverse_region(&mut region, loc, garden_plots, &mut visited_set);
-                price += region.area * region.perimeter;
-                discount_price += region.area * region.fence_sides;
+// Parses a price formula made of numbers, the metrics `area`, `perimeter`, `sides`, `corners`,
+// `holes` and `unit_price`, the operators + - * / and parentheses. `standard` and `bulk` name the
+// puzzle's own formulas.
+fn parse_price_formula(source: &str) -> Result<PriceFormula, String> {
+    let source = match source.trim() {
+        "standard" => "area * perimeter",
+        "bulk" => "area * sides",
+        source => source,
+    };
+
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' => {
+                tokens.push(c.to_string());
+                chars.next();
             }
+            _ if c.is_alphanumeric() || c == '_' || c == '.' => {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+            _ => return Err(format!("Unexpected '{c}' in price formula '{source}'.")),
         }
     }
 
-    (price, discount_price)
+    let mut tokens = tokens.iter().map(String::as_str).peekable();
+    let price_formula = parse_price_sum(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(price_formula),
+        Some(token) => Err(format!("Unexpected '{token}' in price formula '{source}'.")),
+    }
 }
 
-fn traverse_region(
-    region: &mut Region,
-    loc: MapPoint,
-    garden_plots: &Vec<Vec<char>>,
-    visited_set: &mut HashSet<MapPoint>,
-) {
-    visited_set.insert(loc);
-    region.area += 1;
+fn parse_price_sum<'token>(
+    tokens: &mut Peekable<impl Iterator<Item = &'token str>>,
+) -> Result<PriceFormula, String> {
+    let mut price_formula = parse_price_product(tokens)?;
+    while let Some(&operator @ ("+" | "-")) = tokens.peek() {
+        tokens.next();
+        let rhs = Box::new(parse_price_product(tokens)?);
+        price_formula = match operator {
+            "+" => PriceFormula::Add(Box::new(price_formula), rhs),
+            _ => PriceFormula::Subtract(Box::new(price_formula), rhs),
+        };
+    }
+
+    Ok(price_formula)
+}
 
-    // Check Up.
-    if match_plot_type(&loc, Direction::Up, garden_plots) {
-        let up = MapPoint {
-            row: loc.row - 1,
-            col: loc.col,
+fn parse_price_product<'token>(
+    tokens: &mut Peekable<impl Iterator<Item = &'token str>>,
+) -> Result<PriceFormula, String> {
+    let mut price_formula = parse_price_factor(tokens)?;
+    while let Some(&operator @ ("*" | "/")) = tokens.peek() {
+        tokens.next();
+        let rhs = Box::new(parse_price_factor(tokens)?);
+        price_formula = match operator {
+            "*" => PriceFormula::Multiply(Box::new(price_formula), rhs),
+            _ => PriceFormula::Divide(Box::new(price_formula), rhs),
         };
-        if !visited_set.contains(&up) {
-            traverse_region(region, up, garden_plots, visited_set);
+    }
+
+    Ok(price_formula)
+}
+
+fn parse_price_factor<'token>(
+    tokens: &mut Peekable<impl Iterator<Item = &'token str>>,
+) -> Result<PriceFormula, String> {
+    match tokens.next() {
+        Some("(") => {
+            let price_formula = parse_price_sum(tokens)?;
+            match tokens.next() {
+                Some(")") => Ok(price_formula),
+                other => Err(format!("Expected ')' in price formula, found {other:?}.")),
+            }
         }
-    } else {
-        region.perimeter += 1;
-        if check_new_fence_side(&loc, Direction::Up, garden_plots) {
-            region.fence_sides += 1;
+        Some("area") => Ok(PriceFormula::Metric(RegionMetric::Area)),
+        Some("perimeter") => Ok(PriceFormula::Metric(RegionMetric::Perimeter)),
+        Some("sides") => Ok(PriceFormula::Metric(RegionMetric::Sides)),
+        Some("corners") => Ok(PriceFormula::Metric(RegionMetric::Corners)),
+        Some("holes") => Ok(PriceFormula::Metric(RegionMetric::Holes)),
+        Some("unit_price") => Ok(PriceFormula::Metric(RegionMetric::UnitPrice)),
+        Some(token) => match token.parse() {
+            Ok(value) => Ok(PriceFormula::Constant(value)),
+            Err(_) => Err(format!("Unknown metric '{token}' in price formula.")),
+        },
+        None => Err("Price formula ended unexpectedly.".to_string()),
+    }
+}
+
+// Parses one `<plant> <unit price>` pair per line, e.g. `A 2.5`.
+fn parse_unit_prices(source: &str) -> Result<UnitPrices, String> {
+    let mut unit_prices = UnitPrices {
+        by_plant: HashMap::new(),
+    };
+    for line in source.lines().filter(|line| !line.trim().is_empty()) {
+        let mut fields = line.split_whitespace();
+        let mut plant = fields.next().unwrap_or_default().chars();
+        match (
+            plant.next(),
+            plant.next(),
+            fields.next().map(str::parse),
+            fields.next(),
+        ) {
+            (Some(plant), None, Some(Ok(unit_price)), None) => {
+                unit_prices.by_plant.insert(plant, unit_price);
+            }
+            _ => return Err(format!("Invalid unit price '{line}'.")),
         }
     }
 
-    // Check Right.
-    if match_plot_type(&loc, Direction::Right, garden_plots) {
-        let right = MapPoint {
-            row: loc.row,
-            col: loc.col + 1,
-        };
-        if !visited_set.contains(&right) {
-            traverse_region(region, right, garden_plots, visited_set);
+    Ok(unit_prices)
+}
+
+// Flood fills each region in turn with an explicit stack, so the size of a region never affects
+// the call depth, and measures the region's fences along the way.
+fn label_regions(garden_plots: &[Vec<char>]) -> GardenRegions {
+    let num_rows = garden_plots.len();
+    let num_cols = garden_plots.first().map_or(0, |row| row.len());
+    let mut visited = vec![false; num_rows * num_cols];
+    let mut labels = vec![0; num_rows * num_cols];
+    let mut regions = Vec::new();
+
+    let mut plots_to_visit = Vec::new();
+    for start_idx in 0..num_rows * num_cols {
+        if visited[start_idx] {
+            continue;
         }
-    } else {
-        region.perimeter += 1;
-        if check_new_fence_side(&loc, Direction::Right, garden_plots) {
-            region.fence_sides += 1;
+        let label = regions.len();
+        let mut region = Region::new(garden_plots[start_idx / num_cols][start_idx % num_cols]);
+        visited[start_idx] = true;
+        plots_to_visit.push(start_idx);
+
+        while let Some(plot_idx) = plots_to_visit.pop() {
+            let loc = MapPoint {
+                row: plot_idx / num_cols,
+                col: plot_idx % num_cols,
+            };
+            labels[plot_idx] = label;
+            region.area += 1;
+
+            for direction in [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ] {
+                if match_plot_type(&loc, direction, garden_plots) {
+                    let next_idx = match direction {
+                        Direction::Up => plot_idx - num_cols,
+                        Direction::Right => plot_idx + 1,
+                        Direction::Down => plot_idx + num_cols,
+                        Direction::Left => plot_idx - 1,
+                    };
+                    if !visited[next_idx] {
+                        visited[next_idx] = true;
+                        plots_to_visit.push(next_idx);
+                    }
+                } else {
+                    region.perimeter += 1;
+                    if check_new_fence_side(&loc, direction, garden_plots) {
+                        region.fence_sides += 1;
+                    }
+                }
+            }
         }
+        regions.push(region);
+    }
+
+    GardenRegions {
+        num_cols,
+        labels,
+        regions,
     }
+}
 
-    // Check Down.
-    if match_plot_type(&loc, Direction::Down, garden_plots) {
-        let down = MapPoint {
-            row: loc.row + 1,
-            col: loc.col,
+// Collects every fence segment as a directed edge between plot corners, keeping the region on its
+// right, then chains each region's edges into closed loops.
+fn trace_region_polygons(garden_regions: &GardenRegions) -> Vec<RegionPolygons> {
+    let num_cols = garden_regions.num_cols;
+    let num_rows = garden_regions.labels.len() / num_cols.max(1);
+    let corner = |row, col| MapPoint { row, col };
+
+    let mut fence_edges: Vec<BTreeMap<MapPoint, Vec<MapPoint>>> =
+        vec![BTreeMap::new(); garden_regions.regions.len()];
+    for (plot_idx, &label) in garden_regions.labels.iter().enumerate() {
+        let (row, col) = (plot_idx / num_cols, plot_idx % num_cols);
+        let same_region = |next_idx: usize| garden_regions.labels[next_idx] == label;
+        let mut add_edge = |from: MapPoint, to: MapPoint| {
+            fence_edges[label].entry(from).or_default().push(to);
         };
-        if !visited_set.contains(&down) {
-            traverse_region(region, down, garden_plots, visited_set);
+
+        if row == 0 || !same_region(plot_idx - num_cols) {
+            add_edge(corner(row, col), corner(row, col + 1));
         }
-    } else {
-        region.perimeter += 1;
-        if check_new_fence_side(&loc, Direction::Down, garden_plots) {
-            region.fence_sides += 1;
+        if col + 1 == num_cols || !same_region(plot_idx + 1) {
+            add_edge(corner(row, col + 1), corner(row + 1, col + 1));
+        }
+        if row + 1 == num_rows || !same_region(plot_idx + num_cols) {
+            add_edge(corner(row + 1, col + 1), corner(row + 1, col));
+        }
+        if col == 0 || !same_region(plot_idx - 1) {
+            add_edge(corner(row + 1, col), corner(row, col));
         }
     }
 
-    // Check Left.
-    if match_plot_type(&loc, Direction::Left, garden_plots) {
-        let left = MapPoint {
-            row: loc.row,
-            col: loc.col - 1,
+    fence_edges
+        .into_iter()
+        .map(|mut edges| {
+            let mut region_polygons = RegionPolygons {
+                outer: Vec::new(),
+                holes: Vec::new(),
+            };
+            while let Some(fence_loop) = trace_fence_loop(&mut edges) {
+                match polygon_area_x2(&fence_loop) > 0 {
+                    true => region_polygons.outer = fence_loop,
+                    false => region_polygons.holes.push(fence_loop),
+                }
+            }
+            region_polygons
+        })
+        .collect()
+}
+
+// Removes one closed loop from the remaining fence edges, returning its corners. Loops start at
+// the top left remaining corner, which can't be a corner where fences meet diagonally. At those
+// corners the walk turns left, keeping the same neighbouring plot on its left, so that plots
+// outside the region only count as connected through a shared side. Loops never cross each other
+// or themselves, although they can touch at such a corner.
+fn trace_fence_loop(edges: &mut BTreeMap<MapPoint, Vec<MapPoint>>) -> Option<Vec<MapPoint>> {
+    let start = *edges.keys().next()?;
+    let mut fence_loop = Vec::new();
+    let mut vertex = start;
+    let mut heading: Option<(isize, isize)> = None;
+
+    loop {
+        let outgoing = edges.get_mut(&vertex)?;
+        let next_idx = match (outgoing.len(), heading) {
+            (1, _) | (_, None) => 0,
+            (_, Some((row_step, col_step))) => {
+                let left_turn = (-col_step, row_step);
+                outgoing
+                    .iter()
+                    .position(|next| edge_heading(vertex, *next) == left_turn)
+                    .unwrap_or(0)
+            }
         };
-        if !visited_set.contains(&left) {
-            traverse_region(region, left, garden_plots, visited_set);
+        let next = outgoing.swap_remove(next_idx);
+        if outgoing.is_empty() {
+            edges.remove(&vertex);
         }
-    } else {
-        region.perimeter += 1;
-        if check_new_fence_side(&loc, Direction::Left, garden_plots) {
-            region.fence_sides += 1;
+
+        let next_heading = edge_heading(vertex, next);
+        if heading != Some(next_heading) {
+            fence_loop.push(vertex);
+        }
+        heading = Some(next_heading);
+        vertex = next;
+        if vertex == start {
+            return Some(fence_loop);
         }
     }
 }
 
-fn match_plot_type(loc: &MapPoint, direction: Direction, garden_plots: &Vec<Vec<char>>) -> bool {
+fn edge_heading(from: MapPoint, to: MapPoint) -> (isize, isize) {
+    (
+        (to.row as isize - from.row as isize).signum(),
+        (to.col as isize - from.col as isize).signum(),
+    )
+}
+
+// Twice the signed area of a polygon (shoelace formula with x = col and y = row), positive for
+// loops running clockwise on the map.
+fn polygon_area_x2(polygon: &[MapPoint]) -> isize {
+    let mut area = 0;
+    for (idx, vertex) in polygon.iter().enumerate() {
+        let next = polygon[(idx + 1) % polygon.len()];
+        area += vertex.col as isize * next.row as isize - next.col as isize * vertex.row as isize;
+    }
+
+    area
+}
+
+fn region_polygons_to_json(
+    garden_regions: &GardenRegions,
+    region_polygons: &[RegionPolygons],
+) -> String {
+    let polygon_json = |polygon: &[MapPoint]| {
+        let vertices: Vec<String> = polygon
+            .iter()
+            .map(|vertex| format!("{{\"row\": {}, \"col\": {}}}", vertex.row, vertex.col))
+            .collect();
+        format!("[{}]", vertices.join(", "))
+    };
+
+    let mut regions = Vec::new();
+    for (label, (region, polygons)) in garden_regions
+        .regions
+        .iter()
+        .zip(region_polygons)
+        .enumerate()
+    {
+        let holes: Vec<String> = polygons
+            .holes
+            .iter()
+            .map(|hole| format!("        {}", polygon_json(hole)))
+            .collect();
+        regions.push(format!(
+            "    {{\n      \"label\": {label},\n      \"plant\": \"{}\",\n      \"outer\": {},\n      \"holes\": [\n{}\n      ]\n    }}",
+            region.plant,
+            polygon_json(&polygons.outer),
+            holes.join(",\n"),
+        ));
+    }
+
+    format!("{{\n  \"regions\": [\n{}\n  ]\n}}\n", regions.join(",\n"))
+}
+
+// Draws every region as a filled path with its fences as the outline. Holes are cut out with the
+// even-odd fill rule, the regions inside them being drawn on their own.
+fn render_garden_svg(garden_regions: &GardenRegions, region_polygons: &[RegionPolygons]) -> String {
+    let num_cols = garden_regions.num_cols;
+    let num_rows = garden_regions.labels.len() / num_cols.max(1);
+    let mut plant_hues = HashMap::new();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        num_cols * SVG_PLOT_SIZE,
+        num_rows * SVG_PLOT_SIZE
+    );
+    for (region, polygons) in garden_regions.regions.iter().zip(region_polygons) {
+        let num_plants = plant_hues.len();
+        let hue = *plant_hues
+            .entry(region.plant)
+            .or_insert(num_plants * 137 % 360);
+
+        let mut path = String::new();
+        for polygon in std::iter::once(&polygons.outer).chain(polygons.holes.iter()) {
+            for (idx, vertex) in polygon.iter().enumerate() {
+                path += &format!(
+                    "{}{} {} ",
+                    if idx == 0 { "M" } else { "L" },
+                    vertex.col * SVG_PLOT_SIZE,
+                    vertex.row * SVG_PLOT_SIZE
+                );
+            }
+            path += "Z ";
+        }
+        svg += &format!(
+            "  <path d=\"{}\" fill=\"hsl({hue}, 60%, 70%)\" fill-rule=\"evenodd\" stroke=\"black\"><title>{}</title></path>\n",
+            path.trim_end(),
+            region.plant
+        );
+    }
+    svg += "</svg>\n";
+
+    svg
+}
+
+// Finds the region whose hole each region sits in, if any. Regions bordering each other along
+// their outer fences share a hole (or the outside of the garden), so they are grouped with a
+// union-find, and a group is enclosed by the region whose hole fence any of its members borders.
+// A region touching the enclosing region's outside only at a corner still counts as enclosed, as
+// there is no side to pass through.
+fn find_enclosing_regions(
+    garden_regions: &GardenRegions,
+    region_polygons: &[RegionPolygons],
+) -> Vec<Option<usize>> {
+    let num_cols = garden_regions.num_cols;
+    let num_rows = garden_regions.labels.len() / num_cols.max(1);
+    let num_regions = garden_regions.regions.len();
+
+    let mut hole_fences = HashSet::new();
+    let mut group_enclosed_by = vec![None; num_regions];
+    let mut group_parents: Vec<usize> = (0..num_regions).collect();
+    for (label, polygons) in region_polygons.iter().enumerate() {
+        for (from, to) in polygons.holes.iter().flat_map(|hole| fence_segments(hole)) {
+            if let Some(plot_idx) = plot_left_of(from, to, num_rows, num_cols) {
+                group_enclosed_by[garden_regions.labels[plot_idx]] = Some(label);
+            }
+            hole_fences.insert((from.min(to), from.max(to)));
+        }
+    }
+
+    let mut outside_group = vec![false; num_regions];
+    for (label, polygons) in region_polygons.iter().enumerate() {
+        for (from, to) in fence_segments(&polygons.outer) {
+            match plot_left_of(from, to, num_rows, num_cols) {
+                None => outside_group[label] = true,
+                Some(_) if hole_fences.contains(&(from.min(to), from.max(to))) => (),
+                Some(plot_idx) => {
+                    let group = find_group(&mut group_parents, label);
+                    let other_group =
+                        find_group(&mut group_parents, garden_regions.labels[plot_idx]);
+                    group_parents[other_group] = group;
+                }
+            }
+        }
+    }
+
+    // Gather what is known about each group at its root.
+    for label in 0..num_regions {
+        let group = find_group(&mut group_parents, label);
+        outside_group[group] |= outside_group[label];
+        group_enclosed_by[group] = group_enclosed_by[group].or(group_enclosed_by[label]);
+    }
+
+    (0..num_regions)
+        .map(|label| {
+            let group = find_group(&mut group_parents, label);
+            match outside_group[group] {
+                true => None,
+                false => group_enclosed_by[group],
+            }
+        })
+        .collect()
+}
+
+fn find_group(group_parents: &mut [usize], label: usize) -> usize {
+    let mut label = label;
+    while group_parents[label] != label {
+        group_parents[label] = group_parents[group_parents[label]];
+        label = group_parents[label];
+    }
+
+    label
+}
+
+// Splits a polygon's sides into fence segments one plot long.
+fn fence_segments(polygon: &[MapPoint]) -> impl Iterator<Item = (MapPoint, MapPoint)> + '_ {
+    polygon.iter().enumerate().flat_map(|(idx, &from)| {
+        let to = polygon[(idx + 1) % polygon.len()];
+        let (row_step, col_step) = edge_heading(from, to);
+        let num_segments = from.row.abs_diff(to.row) + from.col.abs_diff(to.col);
+        (0..num_segments).map(move |segment_idx| {
+            let corner = |segment_idx: usize| MapPoint {
+                row: (from.row as isize + row_step * segment_idx as isize) as usize,
+                col: (from.col as isize + col_step * segment_idx as isize) as usize,
+            };
+            (corner(segment_idx), corner(segment_idx + 1))
+        })
+    })
+}
+
+// The plot on the left of a fence segment, which is the side facing away from the region that
+// owns the fence, or None if that side is off the map.
+fn plot_left_of(from: MapPoint, to: MapPoint, num_rows: usize, num_cols: usize) -> Option<usize> {
+    let (row, col) = match edge_heading(from, to) {
+        (0, 1) => (from.row.checked_sub(1)?, from.col),
+        (1, 0) => (from.row, from.col),
+        (0, -1) => (from.row, to.col),
+        (-1, 0) => (to.row, from.col.checked_sub(1)?),
+        heading => unreachable!("Fence segments can't head {heading:?}."),
+    };
+
+    (row < num_rows && col < num_cols).then_some(row * num_cols + col)
+}
+
+fn match_plot_type(loc: &MapPoint, direction: Direction, garden_plots: &[Vec<char>]) -> bool {
     match direction {
         Direction::Up => {
             if loc.row == 0 {
@@ -170,7 +763,7 @@ fn match_plot_type(loc: &MapPoint, direction: Direction, garden_plots: &Vec<Vec<
 fn check_new_fence_side(
     loc: &MapPoint,
     fence_direction: Direction,
-    garden_plots: &Vec<Vec<char>>,
+    garden_plots: &[Vec<char>],
 ) -> bool {
     match fence_direction {
         Direction::Up => {
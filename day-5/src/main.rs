@@ -1,5 +1,78 @@
 use std::{collections::HashMap, error::Error, fs};
 
+// Largest update for which valid orderings are counted with the subset DP.
+const MAX_EXACT_COUNT_PAGES: usize = 20;
+// Largest update whose pages fit in the precedence bitmasks.
+const MAX_ORDERED_PAGES: usize = 64;
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum OrderingCount {
+    Exact(u64),
+    AtLeast(u64),
+}
+
+struct ValidOrderings<'update> {
+    pages: &'update [i32],
+    must_precede: Vec<u64>,
+    ordering: Vec<usize>,
+    next_candidate: Vec<usize>,
+    placed: u64,
+    exhausted: bool,
+}
+
+impl<'update> ValidOrderings<'update> {
+    fn new(must_precede: Vec<u64>, update: &'update [i32]) -> Self {
+        ValidOrderings {
+            pages: update,
+            must_precede,
+            ordering: Vec::with_capacity(update.len()),
+            next_candidate: vec![0; update.len() + 1],
+            placed: 0,
+            exhausted: false,
+        }
+    }
+
+    fn backtrack(&mut self) {
+        match self.ordering.pop() {
+            Some(page_idx) => self.placed &= !(1 << page_idx),
+            None => self.exhausted = true,
+        }
+    }
+}
+
+impl Iterator for ValidOrderings<'_> {
+    type Item = Vec<i32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let num_pages = self.pages.len();
+        while !self.exhausted {
+            let depth = self.ordering.len();
+            if depth == num_pages {
+                let valid_ordering = self.ordering.iter().map(|&idx| self.pages[idx]).collect();
+                self.backtrack();
+                return Some(valid_ordering);
+            }
+
+            // A page can be placed once every page that must precede it has been placed.
+            let candidate = (self.next_candidate[depth]..num_pages).find(|&page_idx| {
+                self.placed & (1 << page_idx) == 0
+                    && self.must_precede[page_idx] & !self.placed == 0
+            });
+            match candidate {
+                Some(page_idx) => {
+                    self.next_candidate[depth] = page_idx + 1;
+                    self.next_candidate[depth + 1] = 0;
+                    self.ordering.push(page_idx);
+                    self.placed |= 1 << page_idx;
+                }
+                None => self.backtrack(),
+            }
+        }
+
+        None
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let update_details = fs::read_to_string("day-5/day5_input.txt")?;
     let (page_order_rules, mut updates) = parse_update_details(&update_details);
@@ -25,6 +98,48 @@ fn main() -> Result<(), Box<dyn Error>> {
         "Sum of middle page numbers from corrected invalid updates: {corrected_middle_page_numbers_sum}"
     );
 
+    let ordering_cap = 1_000_000;
+    let mut num_uniquely_ordered_updates = 0;
+    let mut max_ordering_count = OrderingCount::Exact(0);
+    let mut num_uncounted_updates = 0;
+    for update in updates.iter() {
+        let Some(ordering_count) = count_valid_orderings(&page_order_rules, update, ordering_cap)
+        else {
+            num_uncounted_updates += 1;
+            continue;
+        };
+        if ordering_count == OrderingCount::Exact(1) {
+            num_uniquely_ordered_updates += 1;
+        }
+        max_ordering_count = match (max_ordering_count, ordering_count) {
+            (OrderingCount::AtLeast(_), _) => max_ordering_count,
+            (OrderingCount::Exact(max_count), OrderingCount::Exact(count))
+                if max_count >= count =>
+            {
+                max_ordering_count
+            }
+            _ => ordering_count,
+        };
+    }
+
+    println!(
+        "Number of updates with exactly one valid ordering: {num_uniquely_ordered_updates} of {}",
+        updates.len()
+    );
+    match max_ordering_count {
+        OrderingCount::Exact(count) => {
+            println!("Most valid orderings for a single update: {count}")
+        }
+        OrderingCount::AtLeast(count) => {
+            println!("Most valid orderings for a single update: at least {count}")
+        }
+    }
+    if num_uncounted_updates > 0 {
+        println!(
+            "Updates with more than {MAX_ORDERED_PAGES} pages left uncounted: {num_uncounted_updates}"
+        );
+    }
+
     Ok(())
 }
 
@@ -160,3 +275,97 @@ fn correct_invalid_update(page_order_rules: &HashMap<i32, Vec<i32>>, update: &mu
         }
     }
 }
+
+/// Returns, for each page of the update, a bitmask of the update's pages that the order
+/// rules require to be printed before it, or None if the update has more than
+/// MAX_ORDERED_PAGES pages.
+fn build_precedence_masks(
+    page_order_rules: &HashMap<i32, Vec<i32>>,
+    update: &[i32],
+) -> Option<Vec<u64>> {
+    if update.len() > MAX_ORDERED_PAGES {
+        return None;
+    }
+
+    let mut must_precede = vec![0; update.len()];
+    for (earlier_idx, earlier_page) in update.iter().enumerate() {
+        if let Some(order_rules) = page_order_rules.get(earlier_page) {
+            for (later_idx, later_page) in update.iter().enumerate() {
+                if order_rules.contains(later_page) {
+                    must_precede[later_idx] |= 1 << earlier_idx;
+                }
+            }
+        }
+    }
+
+    Some(must_precede)
+}
+
+/// Checks whether the precedence masks contain a cycle by placing every page whose
+/// predecessors are all placed until no more pages can be placed (Kahn's algorithm).
+fn has_precedence_cycle(must_precede: &[u64]) -> bool {
+    let mut placed = 0u64;
+    let mut num_placed = 0;
+    loop {
+        let ready = must_precede
+            .iter()
+            .enumerate()
+            .filter(|&(page_idx, predecessors)| {
+                placed & (1 << page_idx) == 0 && predecessors & !placed == 0
+            })
+            .fold(0u64, |ready, (page_idx, _)| ready | 1 << page_idx);
+        if ready == 0 {
+            return num_placed < must_precede.len();
+        }
+        placed |= ready;
+        num_placed += ready.count_ones() as usize;
+    }
+}
+
+/// Counts the orderings of the update's pages that satisfy every applicable order rule,
+/// stopping once `cap` orderings are known to exist, or None if the update is too long to count.
+/// Updates of up to MAX_EXACT_COUNT_PAGES pages are counted with a DP over the subsets of
+/// placed pages, larger updates by enumerating orderings until the cap is reached.
+fn count_valid_orderings(
+    page_order_rules: &HashMap<i32, Vec<i32>>,
+    update: &[i32],
+    cap: u64,
+) -> Option<OrderingCount> {
+    let must_precede = build_precedence_masks(page_order_rules, update)?;
+    if update.len() > MAX_EXACT_COUNT_PAGES {
+        // Contradictory rules leave no valid ordering, which the enumeration would only find
+        // after backtracking through every prefix.
+        if has_precedence_cycle(&must_precede) {
+            return Some(OrderingCount::Exact(0));
+        }
+        let num_found = ValidOrderings::new(must_precede, update)
+            .take(cap as usize)
+            .count() as u64;
+        return match num_found < cap {
+            true => Some(OrderingCount::Exact(num_found)),
+            false => Some(OrderingCount::AtLeast(cap)),
+        };
+    }
+
+    let all_placed = (1usize << update.len()) - 1;
+    // num_prefixes[placed] is the number of valid ways to print exactly the pages in placed.
+    let mut num_prefixes = vec![0u64; all_placed + 1];
+    num_prefixes[0] = 1;
+    for placed in 0..all_placed {
+        if num_prefixes[placed] == 0 {
+            continue;
+        }
+        for (page_idx, predecessors) in must_precede.iter().enumerate() {
+            let page_bit = 1 << page_idx;
+            if placed & page_bit == 0 && predecessors & !(placed as u64) == 0 {
+                num_prefixes[placed | page_bit] =
+                    num_prefixes[placed | page_bit].saturating_add(num_prefixes[placed]);
+            }
+        }
+    }
+
+    match num_prefixes[all_placed] < cap {
+        true => Some(OrderingCount::Exact(num_prefixes[all_placed])),
+        false => Some(OrderingCount::AtLeast(cap)),
+    }
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashSet, error::Error, fs};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+};
 
 #[derive(Copy, PartialEq, Debug, Clone, Eq, Hash)]
 enum Direction {
@@ -8,55 +13,393 @@ enum Direction {
     Right,
 }
 
-#[derive(Copy, PartialEq, Debug, Clone)]
+#[derive(Copy, PartialEq, Debug, Clone, Eq, Hash)]
 struct MapPoint {
     row: usize,
     col: usize,
 }
 
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum TurnPolicy {
+    Right,
+    Left,
+    // Turns right on the first obstacle, left on the second, and so on.
+    Alternate,
+}
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+struct GuardRules {
+    turn_policy: TurnPolicy,
+    // Guards leaving one edge of the map re-enter from the opposite edge.
+    wrap_around: bool,
+}
+
+impl GuardRules {
+    fn turn(&self, direction: Direction, num_turns: usize) -> Direction {
+        match self.turn_policy {
+            TurnPolicy::Right => rotate_right(direction),
+            TurnPolicy::Left => rotate_left(direction),
+            TurnPolicy::Alternate if num_turns.is_multiple_of(2) => rotate_right(direction),
+            TurnPolicy::Alternate => rotate_left(direction),
+        }
+    }
+
+    // Part of the guard's state needed to know which way it turns next.
+    fn turn_phase(&self, num_turns: usize) -> usize {
+        match self.turn_policy {
+            TurnPolicy::Alternate => num_turns % 2,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+struct GuardState {
+    pos: MapPoint,
+    direction: Direction,
+    num_turns: usize,
+}
+
+struct GuardRoute {
+    // Every state the guard is in, in order, starting with its start state.
+    states: Vec<GuardState>,
+    // Index into states where the guard's route starts repeating, if it loops.
+    loop_start: Option<usize>,
+}
+
+// For every cell and direction, the cell where a guard moving that way from the cell stops
+// in front of an obstacle, or None if the guard never reaches one.
+struct JumpTable {
+    num_rows: usize,
+    num_cols: usize,
+    wrap_around: bool,
+    next_stop: Vec<[Option<MapPoint>; 4]>,
+}
+
+impl JumpTable {
+    fn new(map: &[Vec<char>], guard_rules: GuardRules) -> Self {
+        let num_rows = map.len();
+        let num_cols = map[0].len();
+        let mut next_stop = vec![[None; 4]; num_rows * num_cols];
+
+        // Sweep each row and column against the direction of travel, remembering the cell in
+        // front of the last obstacle passed. Wrapping maps are swept twice so obstacles carry
+        // over the edge.
+        let num_laps = if guard_rules.wrap_around { 2 } else { 1 };
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            let (num_lines, line_len) = match direction {
+                Direction::Up | Direction::Down => (num_cols, num_rows),
+                Direction::Left | Direction::Right => (num_rows, num_cols),
+            };
+            for line in 0..num_lines {
+                let mut stop = None;
+                for sweep_idx in 0..line_len * num_laps {
+                    let along = match direction {
+                        Direction::Up | Direction::Left => sweep_idx % line_len,
+                        Direction::Down | Direction::Right => line_len - 1 - sweep_idx % line_len,
+                    };
+                    let point = match direction {
+                        Direction::Up | Direction::Down => MapPoint {
+                            row: along,
+                            col: line,
+                        },
+                        Direction::Left | Direction::Right => MapPoint {
+                            row: line,
+                            col: along,
+                        },
+                    };
+                    match map[point.row][point.col] {
+                        '#' => {
+                            stop = step(
+                                point,
+                                rotate_right(rotate_right(direction)),
+                                (num_rows, num_cols),
+                                guard_rules.wrap_around,
+                            )
+                        }
+                        _ => next_stop[point.row * num_cols + point.col][direction as usize] = stop,
+                    }
+                }
+            }
+        }
+
+        JumpTable {
+            num_rows,
+            num_cols,
+            wrap_around: guard_rules.wrap_around,
+            next_stop,
+        }
+    }
+
+    fn state_idx(&self, point: MapPoint, direction: Direction, turn_phase: usize) -> usize {
+        ((point.row * self.num_cols + point.col) * 4 + direction as usize) * 2 + turn_phase
+    }
+
+    // Finds where the guard stops, taking the single added obstruction into account.
+    fn next_stop(
+        &self,
+        point: MapPoint,
+        direction: Direction,
+        new_obstruction: MapPoint,
+    ) -> Option<MapPoint> {
+        let stop = self.next_stop[point.row * self.num_cols + point.col][direction as usize];
+        let Some(obstruction_dist) = self.steps_ahead(point, new_obstruction, direction) else {
+            return stop;
+        };
+        let blocks_path = match stop {
+            Some(stop_point) => match self.steps_ahead(point, stop_point, direction) {
+                Some(stop_dist) => obstruction_dist <= stop_dist,
+                None => false,
+            },
+            None => true,
+        };
+        match blocks_path {
+            true if obstruction_dist > 0 => step(
+                new_obstruction,
+                rotate_right(rotate_right(direction)),
+                (self.num_rows, self.num_cols),
+                self.wrap_around,
+            ),
+            _ => stop,
+        }
+    }
+
+    // Returns how many steps in direction it takes to get from `from` to `to`, if `to` lies
+    // straight ahead.
+    fn steps_ahead(&self, from: MapPoint, to: MapPoint, direction: Direction) -> Option<usize> {
+        let (from_along, to_along, line_len) = match direction {
+            Direction::Up | Direction::Down if from.col == to.col => {
+                (from.row, to.row, self.num_rows)
+            }
+            Direction::Left | Direction::Right if from.row == to.row => {
+                (from.col, to.col, self.num_cols)
+            }
+            _ => return None,
+        };
+        // Up and Left move towards lower indexes.
+        let (high, low) = match direction {
+            Direction::Up | Direction::Left => (from_along, to_along),
+            Direction::Down | Direction::Right => (to_along, from_along),
+        };
+        match self.wrap_around {
+            true => Some((high + line_len - low) % line_len),
+            false if high >= low => Some(high - low),
+            false => None,
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let (guard_rules, export_routes) = parse_arguments(env::args().skip(1))?;
+
     let map_source_stream = fs::read_to_string("day-6/day6_input.txt")?;
 
-    let (mut map, guard_start) = generate_map(&map_source_stream);
+    let (mut map, guard_starts) = generate_map(&map_source_stream);
+
+    let start_states: Vec<GuardState> = guard_starts
+        .iter()
+        .map(|&(pos, direction)| GuardState {
+            pos,
+            direction,
+            num_turns: 0,
+        })
+        .collect();
+    let guard_routes: Vec<GuardRoute> = start_states
+        .iter()
+        .map(|&start_state| trace_guard_route(&map, start_state, guard_rules, None))
+        .collect();
 
-    let num_guard_positions = track_guard_positions(&mut map, guard_start);
+    for (guard_idx, guard_route) in guard_routes.iter().enumerate() {
+        let start_pos = guard_route.states[0].pos;
+        let guard_positions: HashSet<MapPoint> =
+            guard_route.states.iter().map(|state| state.pos).collect();
+        println!(
+            "Guard {} at row {}, col {}: {} distinct positions{}",
+            guard_idx + 1,
+            start_pos.row,
+            start_pos.col,
+            guard_positions.len(),
+            if guard_route.loop_start.is_some() {
+                ", loops"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let mut num_guard_positions = 0;
+    for &(guard_start, start_direction) in guard_starts.iter() {
+        num_guard_positions +=
+            track_guard_positions(&mut map, guard_start, start_direction, guard_rules);
+    }
 
     println!("Number of distinct guard positions: {num_guard_positions}");
+    println!(
+        "Any guard loops: {}",
+        guard_routes.iter().any(|route| route.loop_start.is_some())
+    );
 
-    let mut num_loop_options = 0;
-    for row in 0..map.len() {
-        for col in 0..map[row].len() {
-            if row == guard_start.row && col == guard_start.col {
-                continue;
+    // Guards that never reach a candidate obstruction keep their original route, so only guards
+    // passing through it need simulating.
+    let guard_first_visits: Vec<HashMap<MapPoint, GuardState>> = guard_routes
+        .iter()
+        .map(|guard_route| find_first_visits(guard_route).into_iter().collect())
+        .collect();
+    let mut candidates = Vec::new();
+    let mut is_candidate: HashSet<MapPoint> = guard_starts.iter().map(|&(pos, _)| pos).collect();
+    for guard_route in guard_routes.iter() {
+        for (new_obstruction, _) in find_first_visits(guard_route) {
+            if is_candidate.insert(new_obstruction) {
+                candidates.push(new_obstruction);
             }
-            if map[row][col] == 'X'
-                && guard_gets_stuck(&mut map, guard_start, MapPoint { row, col })
-            {
-                num_loop_options += 1;
+        }
+    }
+    // A guard that loops on its own stays trapped by an obstruction it never reaches, so every
+    // free cell becomes a candidate.
+    if guard_routes.iter().any(|route| route.loop_start.is_some()) {
+        for (row, map_row) in map.iter().enumerate() {
+            for (col, &cell) in map_row.iter().enumerate() {
+                if cell != '#' && is_candidate.insert(MapPoint { row, col }) {
+                    candidates.push(MapPoint { row, col });
+                }
             }
         }
     }
 
-    println!("Number of different positions to put obstructions: {num_loop_options}");
+    let jump_table = JumpTable::new(&map, guard_rules);
+    let mut seen_turns = vec![0; map.len() * map[0].len() * 8];
+    let mut search_id = 0;
+    let mut trapping_obstructions = Vec::new();
+    let mut num_trap_all_options = 0;
+    for new_obstruction in candidates {
+        let mut trapped_guards = Vec::new();
+        for (guard_idx, first_visits) in guard_first_visits.iter().enumerate() {
+            let is_trapped = match first_visits.get(&new_obstruction) {
+                Some(&guard_state) => {
+                    search_id += 1;
+                    guard_gets_stuck(
+                        &jump_table,
+                        &mut seen_turns,
+                        search_id,
+                        guard_state,
+                        guard_rules,
+                        new_obstruction,
+                    )
+                }
+                None => guard_routes[guard_idx].loop_start.is_some(),
+            };
+            if is_trapped {
+                trapped_guards.push(guard_idx);
+            }
+        }
+
+        if trapped_guards.len() == guard_starts.len() {
+            num_trap_all_options += 1;
+        }
+        if !trapped_guards.is_empty() {
+            trapping_obstructions.push((new_obstruction, trapped_guards));
+        }
+    }
+
+    println!(
+        "Number of different positions to put obstructions: {}",
+        trapping_obstructions.len()
+    );
+    println!("Number of positions to put obstructions trapping all guards: {num_trap_all_options}");
+
+    // Rendering every loop re-walks each trapping obstruction cell by cell, so it is opt in.
+    if !export_routes {
+        return Ok(());
+    }
+
+    fs::write(
+        "day-6/guard_route.json",
+        guard_routes_to_json(&guard_routes),
+    )?;
+    println!("Wrote guard routes to day-6/guard_route.json");
+
+    let mut loop_renders = String::new();
+    for (new_obstruction, trapped_guards) in trapping_obstructions {
+        for guard_idx in trapped_guards {
+            let looping_route = trace_guard_route(
+                &map,
+                start_states[guard_idx],
+                guard_rules,
+                Some(new_obstruction),
+            );
+            loop_renders.push_str(&format!(
+                "Obstruction at row {}, col {} traps guard {}:\n",
+                new_obstruction.row,
+                new_obstruction.col,
+                guard_idx + 1
+            ));
+            loop_renders.push_str(&render_guard_loop(&map, &looping_route, new_obstruction));
+            loop_renders.push('\n');
+        }
+    }
+    fs::write("day-6/guard_loops.txt", loop_renders)?;
+    println!("Wrote guard loops to day-6/guard_loops.txt");
 
     Ok(())
 }
 
-fn generate_map(source: &str) -> (Vec<Vec<char>>, MapPoint) {
+// Returns the guard rules and whether to export guard routes and render their loops.
+fn parse_arguments(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(GuardRules, bool), Box<dyn Error>> {
+    let mut guard_rules = GuardRules {
+        turn_policy: TurnPolicy::Right,
+        wrap_around: false,
+    };
+    let mut export_routes = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--turn" => {
+                guard_rules.turn_policy = match args.next().as_deref() {
+                    Some("right") => TurnPolicy::Right,
+                    Some("left") => TurnPolicy::Left,
+                    Some("alternate") => TurnPolicy::Alternate,
+                    other => {
+                        return Err(format!(
+                            "Expected 'right', 'left' or 'alternate' after --turn, found {other:?}."
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--wrap" => guard_rules.wrap_around = true,
+            "--export" => export_routes = true,
+            _ => return Err(format!("Unknown argument '{arg}'.").into()),
+        }
+    }
+
+    Ok((guard_rules, export_routes))
+}
+
+fn generate_map(source: &str) -> (Vec<Vec<char>>, Vec<(MapPoint, Direction)>) {
     let mut map = Vec::new();
 
     let mut row_idx = 0;
     let mut col_idx = 0;
 
-    let mut guard_start = MapPoint { row: 0, col: 0 };
+    let mut guard_starts = Vec::new();
 
     let mut current_row = Vec::new();
     for c in source.chars() {
         match c {
-            '.' | '#' | '^' => {
-                if c == '^' {
-                    guard_start.row = row_idx;
-                    guard_start.col = col_idx;
+            '.' | '#' | '^' | '>' | 'v' | '<' => {
+                if let Some(direction) = glyph_direction(c) {
+                    let guard_start = MapPoint {
+                        row: row_idx,
+                        col: col_idx,
+                    };
+                    guard_starts.push((guard_start, direction));
                 }
                 current_row.push(c);
                 col_idx += 1;
@@ -67,106 +410,316 @@ fn generate_map(source: &str) -> (Vec<Vec<char>>, MapPoint) {
                 row_idx += 1;
                 col_idx = 0;
             }
-            _ => unreachable!("Only expect '.', '#', '^', or new line char."),
+            _ => unreachable!("Only expect '.', '#', '^', '>', 'v', '<', or new line char."),
         }
     }
 
-    (map, guard_start)
+    (map, guard_starts)
 }
 
-fn track_guard_positions(map: &mut Vec<Vec<char>>, mut guard_pos: MapPoint) -> i32 {
-    map[guard_pos.row][guard_pos.col] = 'X';
-    let mut num_distinct_positions = 1;
-
-    let mut current_direction = Direction::Up;
-
-    // Creates some maximum iteration value to panic in case of an infinite loop.
-    let mut iter_ctr = 0;
-    let max_iterations = map.len() * map[0].len() * 10;
-    while is_next_move_valid(&map, guard_pos, current_direction) {
-        assert!(iter_ctr < max_iterations);
-        let (next_row, next_col) = match current_direction {
-            Direction::Up => (guard_pos.row - 1, guard_pos.col),
-            Direction::Down => (guard_pos.row + 1, guard_pos.col),
-            Direction::Left => (guard_pos.row, guard_pos.col - 1),
-            Direction::Right => (guard_pos.row, guard_pos.col + 1),
-        };
+fn track_guard_positions(
+    map: &mut [Vec<char>],
+    mut guard_pos: MapPoint,
+    mut current_direction: Direction,
+    guard_rules: GuardRules,
+) -> i32 {
+    // Only count positions not already marked by another guard.
+    let mut num_distinct_positions = 0;
+    if map[guard_pos.row][guard_pos.col] != 'X' {
+        map[guard_pos.row][guard_pos.col] = 'X';
+        num_distinct_positions += 1;
+    }
 
-        match map[next_row][next_col] {
-            '.' => {
-                map[next_row][next_col] = 'X';
-                num_distinct_positions += 1;
-            }
+    // Stop once the guard repeats a turn, or walks further than the map is wide without turning
+    // on a wrapping map, as it will only retrace its route from there.
+    let max_straight_steps = map.len().max(map[0].len());
+    let mut num_straight_steps = 0;
+    let mut num_turns = 0;
+    let mut seen_turns = HashSet::new();
+    while let Some(next_pos) = next_position(map, guard_pos, current_direction, guard_rules) {
+        if num_straight_steps > max_straight_steps {
+            break;
+        }
+        match map[next_pos.row][next_pos.col] {
             '#' => {
-                current_direction = rotate_right(current_direction);
+                let turn_state = (
+                    guard_pos,
+                    current_direction,
+                    guard_rules.turn_phase(num_turns),
+                );
+                if !seen_turns.insert(turn_state) {
+                    break;
+                }
+                current_direction = guard_rules.turn(current_direction, num_turns);
+                num_turns += 1;
+                num_straight_steps = 0;
                 continue;
             }
-            _ => (),
+            'X' => (),
+            _ => {
+                map[next_pos.row][next_pos.col] = 'X';
+                num_distinct_positions += 1;
+            }
         }
 
-        guard_pos.row = next_row;
-        guard_pos.col = next_col;
-        iter_ctr += 1;
+        guard_pos = next_pos;
+        num_straight_steps += 1;
     }
 
     num_distinct_positions
 }
 
+// Returns, for every cell the guard reaches after leaving the start, the cell along with the
+// guard's state just before first entering it.
+fn find_first_visits(guard_route: &GuardRoute) -> Vec<(MapPoint, GuardState)> {
+    let mut first_visits = Vec::new();
+    let mut visited = HashSet::from([guard_route.states[0].pos]);
+
+    for states in guard_route.states.windows(2) {
+        if states[1].num_turns == states[0].num_turns && visited.insert(states[1].pos) {
+            first_visits.push((states[1].pos, states[0]));
+        }
+    }
+
+    first_visits
+}
+
+// Simulates the guard leg by leg with the jump table, recording each turn in seen_turns under
+// search_id so the buffer can be reused across candidates without clearing it.
 fn guard_gets_stuck(
-    map: &mut Vec<Vec<char>>,
-    mut guard_pos: MapPoint,
+    jump_table: &JumpTable,
+    seen_turns: &mut [u32],
+    search_id: u32,
+    mut guard_state: GuardState,
+    guard_rules: GuardRules,
     new_obstruction: MapPoint,
 ) -> bool {
-    // Create new obstruction.
-    map[new_obstruction.row][new_obstruction.col] = '#';
-
-    let mut current_direction = Direction::Up;
-    let mut found_loop = false;
-    let mut loop_tracker = HashSet::new();
-
-    // Creates some maximum iteration value to panic in case of an infinite loop.
-    let mut iter_ctr = 0;
-    let max_iterations = map.len() * map[0].len() * 100;
-    while is_next_move_valid(&map, guard_pos, current_direction) {
-        assert!(iter_ctr < max_iterations);
-        let (next_row, next_col) = match current_direction {
-            Direction::Up => (guard_pos.row - 1, guard_pos.col),
-            Direction::Down => (guard_pos.row + 1, guard_pos.col),
-            Direction::Left => (guard_pos.row, guard_pos.col - 1),
-            Direction::Right => (guard_pos.row, guard_pos.col + 1),
+    while let Some(stop) =
+        jump_table.next_stop(guard_state.pos, guard_state.direction, new_obstruction)
+    {
+        let turn_phase = guard_rules.turn_phase(guard_state.num_turns);
+        let state_idx = jump_table.state_idx(stop, guard_state.direction, turn_phase);
+        if seen_turns[state_idx] == search_id {
+            return true;
+        }
+        seen_turns[state_idx] = search_id;
+
+        guard_state = GuardState {
+            pos: stop,
+            direction: guard_rules.turn(guard_state.direction, guard_state.num_turns),
+            num_turns: guard_state.num_turns + 1,
         };
+    }
 
-        match map[next_row][next_col] {
-            '#' => {
-                current_direction = rotate_right(current_direction);
-                let current_position = (next_row, next_col, current_direction);
-                if !loop_tracker.insert(current_position) {
-                    found_loop = true;
-                    break;
-                }
-                continue;
+    // Without an obstacle ahead, a wrapping guard circles its row or column forever.
+    guard_rules.wrap_around
+}
+
+// Walks the guard one step at a time, optionally with an added obstruction, until it leaves the
+// map or returns to a state it has already been in.
+fn trace_guard_route(
+    map: &[Vec<char>],
+    start_state: GuardState,
+    guard_rules: GuardRules,
+    new_obstruction: Option<MapPoint>,
+) -> GuardRoute {
+    let state_key = |state: GuardState| {
+        (
+            state.pos,
+            state.direction,
+            guard_rules.turn_phase(state.num_turns),
+        )
+    };
+
+    let mut guard_state = start_state;
+    let mut states = vec![guard_state];
+    let mut state_idxs = HashMap::from([(state_key(guard_state), 0)]);
+    let mut loop_start = None;
+
+    while let Some(next_pos) =
+        next_position(map, guard_state.pos, guard_state.direction, guard_rules)
+    {
+        if map[next_pos.row][next_pos.col] == '#' || Some(next_pos) == new_obstruction {
+            guard_state.direction = guard_rules.turn(guard_state.direction, guard_state.num_turns);
+            guard_state.num_turns += 1;
+        } else {
+            guard_state.pos = next_pos;
+        }
+
+        if let Some(&state_idx) = state_idxs.get(&state_key(guard_state)) {
+            loop_start = Some(state_idx);
+            break;
+        }
+        state_idxs.insert(state_key(guard_state), states.len());
+        states.push(guard_state);
+    }
+
+    GuardRoute { states, loop_start }
+}
+
+fn guard_routes_to_json(guard_routes: &[GuardRoute]) -> String {
+    let point_json = |point: MapPoint| format!("\"row\": {}, \"col\": {}", point.row, point.col);
+
+    let mut guards = Vec::new();
+    for guard_route in guard_routes {
+        let start_state = guard_route.states[0];
+        let mut cells = vec![format!(
+            "        {{{}, \"direction\": \"{:?}\"}}",
+            point_json(start_state.pos),
+            start_state.direction
+        )];
+        let mut turns = Vec::new();
+        for states in guard_route.states.windows(2) {
+            let (last_state, state) = (states[0], states[1]);
+            match state.num_turns == last_state.num_turns {
+                true => cells.push(format!(
+                    "        {{{}, \"direction\": \"{:?}\"}}",
+                    point_json(state.pos),
+                    state.direction
+                )),
+                false => turns.push(format!(
+                    "        {{{}, \"from\": \"{:?}\", \"to\": \"{:?}\"}}",
+                    point_json(state.pos),
+                    last_state.direction,
+                    state.direction
+                )),
             }
-            _ => (),
         }
 
-        guard_pos.row = next_row;
-        guard_pos.col = next_col;
-        iter_ctr += 1;
+        guards.push(format!(
+            "    {{\n      \"start\": {{{}}},\n      \"loops\": {},\n      \"cells\": [\n{}\n      ],\n      \"turns\": [\n{}\n      ]\n    }}",
+            point_json(start_state.pos),
+            guard_route.loop_start.is_some(),
+            cells.join(",\n"),
+            turns.join(",\n"),
+        ));
+    }
+
+    format!("{{\n  \"guards\": [\n{}\n  ]\n}}\n", guards.join(",\n"))
+}
+
+// Draws the repeating part of a looping route, cropped to the loop, using '|' and '-' for
+// vertical and horizontal movement, '+' where the guard turns or crosses its own path and 'O'
+// for the added obstruction.
+fn render_guard_loop(
+    map: &[Vec<char>],
+    guard_route: &GuardRoute,
+    new_obstruction: MapPoint,
+) -> String {
+    let loop_states = &guard_route.states[guard_route.loop_start.unwrap_or(0)..];
+
+    let mut drawing: Vec<Vec<char>> = map
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&c| if c == '#' { '#' } else { '.' })
+                .collect()
+        })
+        .collect();
+    for state in loop_states {
+        let path_char = match state.direction {
+            Direction::Up | Direction::Down => '|',
+            Direction::Left | Direction::Right => '-',
+        };
+        let cell = &mut drawing[state.pos.row][state.pos.col];
+        *cell = match *cell {
+            '.' => path_char,
+            c if c == path_char => path_char,
+            _ => '+',
+        };
     }
+    let start_state = guard_route.states[0];
+    drawing[new_obstruction.row][new_obstruction.col] = 'O';
+    drawing[start_state.pos.row][start_state.pos.col] = direction_glyph(start_state.direction);
 
-    // Remove new obstruction.
-    map[new_obstruction.row][new_obstruction.col] = 'X';
+    // Crop to the loop and its obstacles.
+    let min_row = loop_states
+        .iter()
+        .map(|state| state.pos.row)
+        .min()
+        .unwrap_or(0);
+    let max_row = loop_states
+        .iter()
+        .map(|state| state.pos.row)
+        .max()
+        .unwrap_or(0);
+    let min_col = loop_states
+        .iter()
+        .map(|state| state.pos.col)
+        .min()
+        .unwrap_or(0);
+    let max_col = loop_states
+        .iter()
+        .map(|state| state.pos.col)
+        .max()
+        .unwrap_or(0);
+    let row_range = min_row.saturating_sub(1)..=(max_row + 1).min(map.len() - 1);
+    let col_range = min_col.saturating_sub(1)..=(max_col + 1).min(map[0].len() - 1);
+
+    let mut render = String::new();
+    for row in drawing[row_range].iter() {
+        render.extend(&row[col_range.clone()]);
+        render.push('\n');
+    }
 
-    found_loop
+    render
 }
 
-fn is_next_move_valid(map: &Vec<Vec<char>>, point: MapPoint, direction: Direction) -> bool {
+fn next_position(
+    map: &[Vec<char>],
+    point: MapPoint,
+    direction: Direction,
+    guard_rules: GuardRules,
+) -> Option<MapPoint> {
+    step(
+        point,
+        direction,
+        (map.len(), map[0].len()),
+        guard_rules.wrap_around,
+    )
+}
+
+// Moves one cell in direction, returning None when stepping off a non-wrapping map.
+fn step(
+    point: MapPoint,
+    direction: Direction,
+    (num_rows, num_cols): (usize, usize),
+    wrap_around: bool,
+) -> Option<MapPoint> {
+    let (row, col) = match direction {
+        Direction::Up if point.row == 0 && wrap_around => (num_rows - 1, point.col),
+        Direction::Down if point.row + 1 == num_rows && wrap_around => (0, point.col),
+        Direction::Left if point.col == 0 && wrap_around => (point.row, num_cols - 1),
+        Direction::Right if point.col + 1 == num_cols && wrap_around => (point.row, 0),
+        Direction::Up if point.row == 0 => return None,
+        Direction::Down if point.row + 1 == num_rows => return None,
+        Direction::Left if point.col == 0 => return None,
+        Direction::Right if point.col + 1 == num_cols => return None,
+        Direction::Up => (point.row - 1, point.col),
+        Direction::Down => (point.row + 1, point.col),
+        Direction::Left => (point.row, point.col - 1),
+        Direction::Right => (point.row, point.col + 1),
+    };
+
+    Some(MapPoint { row, col })
+}
+
+fn glyph_direction(glyph: char) -> Option<Direction> {
+    match glyph {
+        '^' => Some(Direction::Up),
+        'v' => Some(Direction::Down),
+        '<' => Some(Direction::Left),
+        '>' => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn direction_glyph(direction: Direction) -> char {
     match direction {
-        Direction::Up if point.row == 0 => false,
-        Direction::Down if point.row + 1 == map.len() => false,
-        Direction::Left if point.col == 0 => false,
-        Direction::Right if point.col + 1 == map[0].len() => false,
-        _ => true,
+        Direction::Up => '^',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+        Direction::Right => '>',
     }
 }
 
@@ -178,3 +731,12 @@ fn rotate_right(direction: Direction) -> Direction {
         Direction::Left => Direction::Up,
     }
 }
+
+fn rotate_left(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Left,
+        Direction::Left => Direction::Down,
+        Direction::Down => Direction::Right,
+        Direction::Right => Direction::Up,
+    }
+}
@@ -1,44 +1,771 @@
-use std::{error::Error, fs};
+use std::{cmp::Ordering, collections::HashMap, env, error::Error, fmt, fs};
+
+#[derive(Copy, PartialEq, Debug, Clone)]
+enum WitnessReport {
+    // The first operator sequence found for each equation.
+    First,
+    All,
+    Count,
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
     let equations_source = fs::read_to_string("day-7/day7_input.txt")?;
-    let equations = parse_equations(&equations_source);
 
-    let mut calibration_result_two_operators = 0;
-    for equation in equations.iter() {
-        let mut operator_sequence = Vec::new();
-        let operators = ['+', '*'];
-        calibration_result_two_operators +=
-            produce_test_result(equation, &mut operator_sequence, &operators);
+    let mut operator_sets = Vec::new();
+    let mut witness_report = None;
+    let mut arbitrary_precision = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--operators" => {
+                let symbols = args
+                    .next()
+                    .ok_or("Expected a comma separated list of operators after --operators.")?;
+                operator_sets.push((format!("operators {symbols}"), symbols));
+            }
+            "--report" => {
+                witness_report = match args.next().as_deref() {
+                    Some("first") => Some(WitnessReport::First),
+                    Some("all") => Some(WitnessReport::All),
+                    Some("count") => Some(WitnessReport::Count),
+                    other => {
+                        return Err(format!(
+                            "Expected 'first', 'all' or 'count' after --report, found {other:?}."
+                        )
+                        .into())
+                    }
+                }
+            }
+            "--arbitrary-precision" => arbitrary_precision = true,
+            _ => return Err(format!("Unknown argument '{arg}'.").into()),
+        }
+    }
+    if operator_sets.is_empty() {
+        operator_sets.push((String::from("two operators"), String::from("+,*")));
+        operator_sets.push((String::from("three operators"), String::from("+,*,||")));
     }
 
-    println!("Total calibration results with two operators: {calibration_result_two_operators}");
-
-    let mut calibration_result_three_operators = 0;
-    for equation in equations.iter() {
-        let mut operator_sequence = Vec::new();
-        let operators = ['+', '*', '|'];
-        calibration_result_three_operators +=
-            produce_test_result(equation, &mut operator_sequence, &operators);
+    match arbitrary_precision {
+        true => run_calibration::<BigInt>(&equations_source, &operator_sets, witness_report),
+        false => run_calibration::<i64>(&equations_source, &operator_sets, witness_report),
     }
+}
 
-    println!(
-        "Total calibration results with three operators: {calibration_result_three_operators}"
-    );
+fn run_calibration<N: CalibrationValue + 'static>(
+    equations_source: &str,
+    operator_sets: &[(String, String)],
+    witness_report: Option<WitnessReport>,
+) -> Result<(), Box<dyn Error>> {
+    let equations = parse_equations::<N>(equations_source)?;
+
+    let operator_registry = OperatorRegistry::with_default_operators();
+
+    for (description, symbols) in operator_sets {
+        let operators = operator_registry.select(symbols)?;
+
+        if let Some(witness_report) = witness_report {
+            println!("Equations with {description}:");
+            print_witness_report(&equations, &operators, witness_report);
+        }
+
+        let mut calibration_result = N::from_u32(0);
+        for equation in equations.iter() {
+            calibration_result = calibration_result
+                .try_add(&produce_test_result(equation, &operators))
+                .ok_or("Total calibration result overflowed, try --arbitrary-precision.")?;
+        }
+
+        println!("Total calibration results with {description}: {calibration_result}");
+    }
 
     Ok(())
 }
 
+fn print_witness_report<N: CalibrationValue>(
+    equations: &[Equation<N>],
+    operators: &[&dyn Operator<N>],
+    witness_report: WitnessReport,
+) {
+    let mut unsolvable_equations = Vec::new();
+    for equation in equations {
+        match witness_report {
+            WitnessReport::First => match find_witness(equation, operators) {
+                Some(witness) => println!("{}", format_witness(equation, &witness)),
+                None => unsolvable_equations.push(equation),
+            },
+            WitnessReport::All => {
+                let witnesses = find_all_witnesses(equation, operators);
+                for witness in witnesses.iter() {
+                    println!("{}", format_witness(equation, witness));
+                }
+                if witnesses.is_empty() {
+                    unsolvable_equations.push(equation);
+                }
+            }
+            WitnessReport::Count => match count_witnesses(equation, operators) {
+                0 => unsolvable_equations.push(equation),
+                1 => println!("{}: 1 witness", equation.result),
+                num_witnesses => println!("{}: {num_witnesses} witnesses", equation.result),
+            },
+        }
+    }
+
+    println!("Unsolvable equations: {}", unsolvable_equations.len());
+    for equation in unsolvable_equations {
+        let operands: Vec<String> = equation.operands.iter().map(|op| op.to_string()).collect();
+        println!("{}: {}", equation.result, operands.join(" "));
+    }
+}
+
+// Writes out an equation with its operators filled in, e.g. `190 = 10 * 19`.
+fn format_witness<N: CalibrationValue>(
+    equation: &Equation<N>,
+    witness: &[&dyn Operator<N>],
+) -> String {
+    let mut formatted = format!("{} = {}", equation.result, equation.operands[0]);
+    for (operator, operand) in witness.iter().zip(&equation.operands[1..]) {
+        formatted.push_str(&format!(" {} {operand}", operator.symbol()));
+    }
+
+    formatted
+}
+
+// Numbers calibration equations are solved over. Operations return None instead of overflowing
+// or producing a value the type can't represent, which the solver treats as a dead end.
+trait CalibrationValue: Sized + Clone + PartialEq + PartialOrd + fmt::Display {
+    fn from_digits(digits: &str) -> Option<Self>;
+    fn from_u32(value: u32) -> Self;
+    fn try_add(&self, rhs: &Self) -> Option<Self>;
+    fn try_sub(&self, rhs: &Self) -> Option<Self>;
+    fn try_mul(&self, rhs: &Self) -> Option<Self>;
+    // Quotient rounded towards zero, along with the remainder.
+    fn try_div_rem(&self, rhs: &Self) -> Option<(Self, Self)>;
+    fn try_pow(&self, exponent: &Self) -> Option<Self>;
+    fn try_xor(&self, rhs: &Self) -> Option<Self>;
+}
+
+impl CalibrationValue for i64 {
+    fn from_digits(digits: &str) -> Option<Self> {
+        digits.parse().ok()
+    }
+
+    fn from_u32(value: u32) -> Self {
+        i64::from(value)
+    }
+
+    fn try_add(&self, rhs: &Self) -> Option<Self> {
+        self.checked_add(*rhs)
+    }
+
+    fn try_sub(&self, rhs: &Self) -> Option<Self> {
+        self.checked_sub(*rhs)
+    }
+
+    fn try_mul(&self, rhs: &Self) -> Option<Self> {
+        self.checked_mul(*rhs)
+    }
+
+    fn try_div_rem(&self, rhs: &Self) -> Option<(Self, Self)> {
+        Some((self.checked_div(*rhs)?, self.checked_rem(*rhs)?))
+    }
+
+    fn try_pow(&self, exponent: &Self) -> Option<Self> {
+        self.checked_pow(u32::try_from(*exponent).ok()?)
+    }
+
+    fn try_xor(&self, rhs: &Self) -> Option<Self> {
+        Some(self ^ rhs)
+    }
+}
+
+// Largest power, in bits, computed in arbitrary precision mode.
+const MAX_POWER_BITS: u64 = 1 << 16;
+
+// Arbitrary precision unsigned integer, stored as little endian base 2^32 limbs without any
+// trailing zero limbs.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn normalized(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    fn bit_len(&self) -> u64 {
+        match self.limbs.last() {
+            Some(top_limb) => self.limbs.len() as u64 * 32 - u64::from(top_limb.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    fn bit(&self, bit_idx: u64) -> u32 {
+        (self.limbs[(bit_idx / 32) as usize] >> (bit_idx % 32)) & 1
+    }
+
+    fn mul_small_add(&self, factor: u32, addend: u32) -> Self {
+        let mut limbs = Vec::with_capacity(self.limbs.len() + 1);
+        let mut carry = u64::from(addend);
+        for &limb in self.limbs.iter() {
+            let product = u64::from(limb) * u64::from(factor) + carry;
+            limbs.push(product as u32);
+            carry = product >> 32;
+        }
+        limbs.push(carry as u32);
+
+        BigUint { limbs }.normalized()
+    }
+
+    fn div_rem_small(&self, divisor: u32) -> (Self, u32) {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut remainder = 0u64;
+        for (limb_idx, &limb) in self.limbs.iter().enumerate().rev() {
+            let dividend = (remainder << 32) | u64::from(limb);
+            limbs[limb_idx] = (dividend / u64::from(divisor)) as u32;
+            remainder = dividend % u64::from(divisor);
+        }
+
+        (BigUint { limbs }.normalized(), remainder as u32)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Split into base 10^9 chunks, least significant first.
+        let mut chunks = Vec::new();
+        let mut remaining = self.clone();
+        while !remaining.limbs.is_empty() {
+            let (quotient, chunk) = remaining.div_rem_small(1_000_000_000);
+            chunks.push(chunk);
+            remaining = quotient;
+        }
+
+        match chunks.split_last() {
+            Some((top_chunk, lower_chunks)) => {
+                write!(f, "{top_chunk}")?;
+                for chunk in lower_chunks.iter().rev() {
+                    write!(f, "{chunk:09}")?;
+                }
+                Ok(())
+            }
+            None => write!(f, "0"),
+        }
+    }
+}
+
+// Magnitude arithmetic for BigInt, following the CalibrationValue signatures.
+impl BigUint {
+    fn from_digits(digits: &str) -> Option<Self> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut value = BigUint { limbs: Vec::new() };
+        for c in digits.chars() {
+            value = value.mul_small_add(10, c.to_digit(10)?);
+        }
+        Some(value)
+    }
+
+    fn from_u32(value: u32) -> Self {
+        BigUint { limbs: vec![value] }.normalized()
+    }
+
+    fn try_add(&self, rhs: &Self) -> Option<Self> {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(rhs.limbs.len()) + 1);
+        let mut carry = 0u64;
+        for limb_idx in 0..self.limbs.len().max(rhs.limbs.len()) {
+            let sum = u64::from(self.limbs.get(limb_idx).copied().unwrap_or(0))
+                + u64::from(rhs.limbs.get(limb_idx).copied().unwrap_or(0))
+                + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+
+        Some(BigUint { limbs }.normalized())
+    }
+
+    // Returns None for negative differences, as only non-negative values are representable.
+    fn try_sub(&self, rhs: &Self) -> Option<Self> {
+        if self < rhs {
+            return None;
+        }
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for (limb_idx, &limb) in self.limbs.iter().enumerate() {
+            let mut difference =
+                i64::from(limb) - i64::from(rhs.limbs.get(limb_idx).copied().unwrap_or(0)) - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            limbs.push(difference as u32);
+        }
+
+        Some(BigUint { limbs }.normalized())
+    }
+
+    fn try_mul(&self, rhs: &Self) -> Option<Self> {
+        let mut limbs = vec![0u32; self.limbs.len() + rhs.limbs.len()];
+        for (lhs_idx, &lhs_limb) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (rhs_idx, &rhs_limb) in rhs.limbs.iter().enumerate() {
+                let product = u64::from(lhs_limb) * u64::from(rhs_limb)
+                    + u64::from(limbs[lhs_idx + rhs_idx])
+                    + carry;
+                limbs[lhs_idx + rhs_idx] = product as u32;
+                carry = product >> 32;
+            }
+            limbs[lhs_idx + rhs.limbs.len()] = carry as u32;
+        }
+
+        Some(BigUint { limbs }.normalized())
+    }
+
+    fn try_div_rem(&self, rhs: &Self) -> Option<(Self, Self)> {
+        match rhs.limbs.len() {
+            0 => return None,
+            1 => {
+                let (quotient, remainder) = self.div_rem_small(rhs.limbs[0]);
+                return Some((quotient, BigUint::from_u32(remainder)));
+            }
+            _ => (),
+        }
+
+        // Binary long division.
+        let mut quotient = vec![0u32; self.limbs.len()];
+        let mut remainder = BigUint { limbs: Vec::new() };
+        for bit_idx in (0..self.bit_len()).rev() {
+            remainder = remainder.mul_small_add(2, self.bit(bit_idx));
+            if remainder >= *rhs {
+                remainder = remainder.try_sub(rhs)?;
+                quotient[(bit_idx / 32) as usize] |= 1 << (bit_idx % 32);
+            }
+        }
+
+        Some((BigUint { limbs: quotient }.normalized(), remainder))
+    }
+
+    fn try_pow(&self, exponent: &Self) -> Option<Self> {
+        let mut exponent = match exponent.limbs.len() {
+            0 => 0,
+            1 => exponent.limbs[0],
+            _ => return None,
+        };
+        if self.bit_len() * u64::from(exponent) > MAX_POWER_BITS {
+            return None;
+        }
+
+        let mut power = BigUint::from_u32(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                power = power.try_mul(&base)?;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = base.try_mul(&base)?;
+            }
+        }
+
+        Some(power)
+    }
+
+    fn try_xor(&self, rhs: &Self) -> Option<Self> {
+        let limbs = (0..self.limbs.len().max(rhs.limbs.len()))
+            .map(|limb_idx| {
+                self.limbs.get(limb_idx).copied().unwrap_or(0)
+                    ^ rhs.limbs.get(limb_idx).copied().unwrap_or(0)
+            })
+            .collect();
+
+        Some(BigUint { limbs }.normalized())
+    }
+}
+
+// Arbitrary precision signed integer as a sign and magnitude, so that it follows the same
+// arithmetic as i64 without the range limit.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct BigInt {
+    negative: bool,
+    magnitude: BigUint,
+}
+
+impl BigInt {
+    // Zero is never negative, so that equal values compare equal.
+    fn new(negative: bool, magnitude: BigUint) -> Self {
+        BigInt {
+            negative: negative && !magnitude.limbs.is_empty(),
+            magnitude,
+        }
+    }
+
+    fn negated(&self) -> Self {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+
+    // Bits of the value in two's complement, where -x is stored as !(x - 1) with infinitely
+    // many leading ones, so only the bits below those are returned.
+    fn complement_bits(&self) -> Option<BigUint> {
+        match self.negative {
+            true => self.magnitude.try_sub(&BigUint::from_u32(1)),
+            false => Some(self.magnitude.clone()),
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.magnitude)
+    }
+}
+
+impl CalibrationValue for BigInt {
+    fn from_digits(digits: &str) -> Option<Self> {
+        Some(BigInt::new(false, BigUint::from_digits(digits)?))
+    }
+
+    fn from_u32(value: u32) -> Self {
+        BigInt::new(false, BigUint::from_u32(value))
+    }
+
+    fn try_add(&self, rhs: &Self) -> Option<Self> {
+        if self.negative == rhs.negative {
+            return Some(BigInt::new(
+                self.negative,
+                self.magnitude.try_add(&rhs.magnitude)?,
+            ));
+        }
+        match self.magnitude >= rhs.magnitude {
+            true => Some(BigInt::new(
+                self.negative,
+                self.magnitude.try_sub(&rhs.magnitude)?,
+            )),
+            false => Some(BigInt::new(
+                rhs.negative,
+                rhs.magnitude.try_sub(&self.magnitude)?,
+            )),
+        }
+    }
+
+    fn try_sub(&self, rhs: &Self) -> Option<Self> {
+        self.try_add(&rhs.negated())
+    }
+
+    fn try_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(BigInt::new(
+            self.negative != rhs.negative,
+            self.magnitude.try_mul(&rhs.magnitude)?,
+        ))
+    }
+
+    // The remainder takes the sign of the dividend, as with i64.
+    fn try_div_rem(&self, rhs: &Self) -> Option<(Self, Self)> {
+        let (quotient, remainder) = self.magnitude.try_div_rem(&rhs.magnitude)?;
+        Some((
+            BigInt::new(self.negative != rhs.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    fn try_pow(&self, exponent: &Self) -> Option<Self> {
+        if exponent.negative {
+            return None;
+        }
+        let is_odd_exponent = exponent
+            .magnitude
+            .limbs
+            .first()
+            .is_some_and(|limb| limb & 1 == 1);
+        Some(BigInt::new(
+            self.negative && is_odd_exponent,
+            self.magnitude.try_pow(&exponent.magnitude)?,
+        ))
+    }
+
+    fn try_xor(&self, rhs: &Self) -> Option<Self> {
+        let bits = self.complement_bits()?.try_xor(&rhs.complement_bits()?)?;
+        match self.negative != rhs.negative {
+            // The leading ones of exactly one side flip the result negative.
+            true => Some(BigInt::new(true, bits.try_add(&BigUint::from_u32(1))?)),
+            false => Some(BigInt::new(false, bits)),
+        }
+    }
+}
+
+// Outcome of working out which lhs an operator needs to turn rhs into a given result.
+enum Inverse<N> {
+    Lhs(N),
+    // No lhs produces the result.
+    Impossible,
+    // The operator can't be undone, so the lhs has to be searched for forwards.
+    Unsupported,
+}
+
+impl<N> From<Option<N>> for Inverse<N> {
+    fn from(lhs: Option<N>) -> Self {
+        match lhs {
+            Some(lhs) => Inverse::Lhs(lhs),
+            None => Inverse::Impossible,
+        }
+    }
+}
+
+trait Operator<N> {
+    fn symbol(&self) -> String;
+
+    // Returns None when the operator is undefined for the given operands.
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N>;
+
+    fn undo(&self, _result: &N, _rhs: &N) -> Inverse<N> {
+        Inverse::Unsupported
+    }
+}
+
+type OperatorSequence<'op, N> = [&'op dyn Operator<N>];
+
+struct Add;
+
+impl<N: CalibrationValue> Operator<N> for Add {
+    fn symbol(&self) -> String {
+        String::from("+")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_add(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: &N) -> Inverse<N> {
+        result.try_sub(rhs).into()
+    }
+}
+
+struct Multiply;
+
+impl<N: CalibrationValue> Operator<N> for Multiply {
+    fn symbol(&self) -> String {
+        String::from("*")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_mul(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: &N) -> Inverse<N> {
+        let zero = N::from_u32(0);
+        match result.try_div_rem(rhs) {
+            Some((lhs, remainder)) if remainder == zero => Inverse::Lhs(lhs),
+            // Any lhs works when multiplying zero by zero.
+            None if *rhs == zero && *result == zero => Inverse::Unsupported,
+            _ => Inverse::Impossible,
+        }
+    }
+}
+
+struct Subtract;
+
+impl<N: CalibrationValue> Operator<N> for Subtract {
+    fn symbol(&self) -> String {
+        String::from("-")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_sub(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: &N) -> Inverse<N> {
+        result.try_add(rhs).into()
+    }
+}
+
+// Integer division, rounding towards zero.
+struct Divide;
+
+impl<N: CalibrationValue> Operator<N> for Divide {
+    fn symbol(&self) -> String {
+        String::from("/")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_div_rem(rhs).map(|(quotient, _)| quotient)
+    }
+}
+
+struct Power;
+
+impl<N: CalibrationValue> Operator<N> for Power {
+    fn symbol(&self) -> String {
+        String::from("^")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_pow(rhs)
+    }
+}
+
+struct Xor;
+
+impl<N: CalibrationValue> Operator<N> for Xor {
+    fn symbol(&self) -> String {
+        String::from("xor")
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.try_xor(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: &N) -> Inverse<N> {
+        result.try_xor(rhs).into()
+    }
+}
+
+// Appends the digits of rhs to lhs, both written in the given base.
+struct Concat {
+    base: u32,
+}
+
+impl<N: CalibrationValue> Operator<N> for Concat {
+    fn symbol(&self) -> String {
+        match self.base {
+            10 => String::from("||"),
+            base => format!("||{base}"),
+        }
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        let zero = N::from_u32(0);
+        if *lhs < zero || *rhs < zero {
+            return None;
+        }
+        lhs.try_mul(&self.digit_shift(rhs)?)?.try_add(rhs)
+    }
+
+    fn undo(&self, result: &N, rhs: &N) -> Inverse<N> {
+        let zero = N::from_u32(0);
+        if *result < zero || *rhs < zero {
+            return Inverse::Impossible;
+        }
+        // The result's trailing digits have to match rhs.
+        let Some(shift) = self.digit_shift(rhs) else {
+            return Inverse::Impossible;
+        };
+        match result.try_div_rem(&shift) {
+            Some((lhs, remainder)) if remainder == *rhs => Inverse::Lhs(lhs),
+            _ => Inverse::Impossible,
+        }
+    }
+}
+
+impl Concat {
+    // Power of the base that shifts lhs left by the number of digits in rhs, found by counting
+    // digits rather than formatting the numbers.
+    fn digit_shift<N: CalibrationValue>(&self, rhs: &N) -> Option<N> {
+        let base = N::from_u32(self.base);
+        let mut shift = base.clone();
+        while shift <= *rhs {
+            shift = shift.try_mul(&base)?;
+        }
+        Some(shift)
+    }
+}
+
+struct OperatorRegistry<N> {
+    operators: HashMap<String, Box<dyn Operator<N>>>,
+}
+
+impl<N: CalibrationValue + 'static> OperatorRegistry<N> {
+    fn with_default_operators() -> Self {
+        let mut registry = OperatorRegistry {
+            operators: HashMap::new(),
+        };
+
+        registry.register(Box::new(Add));
+        registry.register(Box::new(Multiply));
+        registry.register(Box::new(Subtract));
+        registry.register(Box::new(Divide));
+        registry.register(Box::new(Power));
+        registry.register(Box::new(Xor));
+        for base in 2..=16 {
+            registry.register(Box::new(Concat { base }));
+        }
+
+        registry
+    }
+
+    // Registers an operator under its symbol, replacing any operator with the same symbol.
+    fn register(&mut self, operator: Box<dyn Operator<N>>) {
+        self.operators.insert(operator.symbol(), operator);
+    }
+
+    fn select(&self, symbols: &str) -> Result<Vec<&dyn Operator<N>>, String> {
+        symbols
+            .split(',')
+            .map(|symbol| {
+                self.operators
+                    .get(symbol.trim())
+                    .map(|operator| operator.as_ref())
+                    .ok_or(format!("Unknown operator '{}'.", symbol.trim()))
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
-struct Equation {
-    result: i64,
-    operands: Vec<i64>,
+struct Equation<N> {
+    result: N,
+    operands: Vec<N>,
 }
 
-fn parse_equations(data_input: &str) -> Vec<Equation> {
+fn parse_equations<N: CalibrationValue>(data_input: &str) -> Result<Vec<Equation<N>>, String> {
+    let parse_value = |digits: &str| {
+        N::from_digits(digits).ok_or(format!(
+            "Failed to parse {digits}, try --arbitrary-precision for values beyond 64 bits."
+        ))
+    };
+
     let mut equations = Vec::new();
 
-    let mut row_result = 0;
+    let mut row_result = N::from_u32(0);
     let mut row_operands = Vec::new();
 
     let mut num_start_idx = 0;
@@ -48,40 +775,21 @@ fn parse_equations(data_input: &str) -> Vec<Equation> {
         match c {
             '0'..='9' => current_idx += 1,
             ':' => {
-                row_result = data_input[num_start_idx..current_idx]
-                    .parse::<i64>()
-                    .expect(&format!(
-                        "Failed to parse {} to an i64.",
-                        &data_input[num_start_idx..current_idx]
-                    ));
+                row_result = parse_value(&data_input[num_start_idx..current_idx])?;
                 // Consume the white space that comes after a colon char.
                 data_iter.next();
                 current_idx += 2;
                 num_start_idx = current_idx;
             }
             ' ' => {
-                row_operands.push(
-                    data_input[num_start_idx..current_idx]
-                        .parse::<i64>()
-                        .expect(&format!(
-                            "Failed to parse {} to an i64.",
-                            &data_input[num_start_idx..current_idx]
-                        )),
-                );
+                row_operands.push(parse_value(&data_input[num_start_idx..current_idx])?);
                 current_idx += 1;
                 num_start_idx = current_idx;
             }
             '\n' => {
-                row_operands.push(
-                    data_input[num_start_idx..current_idx]
-                        .parse::<i64>()
-                        .expect(&format!(
-                            "Failed to parse {} to an i64.",
-                            &data_input[num_start_idx..current_idx]
-                        )),
-                );
+                row_operands.push(parse_value(&data_input[num_start_idx..current_idx])?);
                 let new_equation = Equation {
-                    result: row_result,
+                    result: row_result.clone(),
                     operands: row_operands,
                 };
                 equations.push(new_equation);
@@ -94,41 +802,173 @@ fn parse_equations(data_input: &str) -> Vec<Equation> {
         }
     }
 
-    equations
+    Ok(equations)
 }
 
-fn produce_test_result(
-    equation: &Equation,
-    operator_sequence: &mut Vec<char>,
-    operators: &[char],
-) -> i64 {
-    if operator_sequence.len() == equation.operands.len() - 1 {
-        let mut res = equation.operands[0];
-        for idx in 0..operator_sequence.len() {
-            match operator_sequence[idx] {
-                '+' => res += equation.operands[idx + 1],
-                '*' => res *= equation.operands[idx + 1],
-                '|' => {
-                    res = format!("{res}{}", equation.operands[idx + 1])
-                        .parse::<i64>()
-                        .unwrap()
-                }
-                _ => panic!("Cannot handle operator {}", operator_sequence[idx]),
-            }
+fn produce_test_result<N: CalibrationValue>(
+    equation: &Equation<N>,
+    operators: &[&dyn Operator<N>],
+) -> N {
+    match find_witness(equation, operators) {
+        Some(_) => equation.result.clone(),
+        None => N::from_u32(0),
+    }
+}
+
+fn find_witness<'op, N: CalibrationValue>(
+    equation: &Equation<N>,
+    operators: &[&'op dyn Operator<N>],
+) -> Option<Vec<&'op dyn Operator<N>>> {
+    let mut witness = None;
+    visit_witnesses(
+        &equation.result,
+        &equation.operands,
+        operators,
+        &mut Vec::new(),
+        &mut |operator_sequence| {
+            witness = Some(operator_sequence.to_vec());
+            false
+        },
+    );
+
+    witness
+}
+
+fn find_all_witnesses<'op, N: CalibrationValue>(
+    equation: &Equation<N>,
+    operators: &[&'op dyn Operator<N>],
+) -> Vec<Vec<&'op dyn Operator<N>>> {
+    let mut witnesses = Vec::new();
+    visit_witnesses(
+        &equation.result,
+        &equation.operands,
+        operators,
+        &mut Vec::new(),
+        &mut |operator_sequence| {
+            witnesses.push(operator_sequence.to_vec());
+            true
+        },
+    );
+
+    witnesses
+}
+
+fn count_witnesses<N: CalibrationValue>(
+    equation: &Equation<N>,
+    operators: &[&dyn Operator<N>],
+) -> usize {
+    let mut num_witnesses = 0;
+    visit_witnesses(
+        &equation.result,
+        &equation.operands,
+        operators,
+        &mut Vec::new(),
+        &mut |_| {
+            num_witnesses += 1;
+            true
+        },
+    );
+
+    num_witnesses
+}
+
+// Works backwards from the target, undoing the operator applied to the last operand and
+// recursing on the remaining operands, so branches that can't reach the target are dropped
+// without evaluating them. Every operator sequence producing the target is passed to
+// on_witness, which returns whether to keep searching. undone_operators holds the operators
+// undone so far, last operator first.
+// Returns false once on_witness has stopped the search.
+fn visit_witnesses<'op, N: CalibrationValue>(
+    target: &N,
+    operands: &[N],
+    operators: &[&'op dyn Operator<N>],
+    undone_operators: &mut Vec<&'op dyn Operator<N>>,
+    on_witness: &mut dyn FnMut(&OperatorSequence<'op, N>) -> bool,
+) -> bool {
+    let Some((last_operand, remaining_operands)) = operands.split_last() else {
+        return true;
+    };
+    if remaining_operands.is_empty() {
+        if last_operand != target {
+            return true;
         }
-        if res == equation.result {
-            return res;
+        let witness: Vec<&dyn Operator<N>> = undone_operators.iter().rev().copied().collect();
+        return on_witness(&witness);
+    }
+
+    for &operator in operators {
+        let keep_searching = match operator.undo(target, last_operand) {
+            Inverse::Lhs(lhs) => {
+                undone_operators.push(operator);
+                let keep_searching = visit_witnesses(
+                    &lhs,
+                    remaining_operands,
+                    operators,
+                    undone_operators,
+                    on_witness,
+                );
+                undone_operators.pop();
+                keep_searching
+            }
+            Inverse::Impossible => true,
+            Inverse::Unsupported => visit_forward(
+                &remaining_operands[0],
+                &remaining_operands[1..],
+                operators,
+                &mut Vec::new(),
+                &mut |lhs, operator_sequence| {
+                    if operator.apply(lhs, last_operand).as_ref() != Some(target) {
+                        return true;
+                    }
+                    let witness: Vec<&dyn Operator<N>> = operator_sequence
+                        .iter()
+                        .copied()
+                        .chain([operator])
+                        .chain(undone_operators.iter().rev().copied())
+                        .collect();
+                    on_witness(&witness)
+                },
+            ),
+        };
+        if !keep_searching {
+            return false;
         }
-        return 0;
     }
 
-    for &op in operators {
-        operator_sequence.push(op);
-        if produce_test_result(equation, operator_sequence, operators) == equation.result {
-            return equation.result;
+    true
+}
+
+// Evaluates every operator sequence over the operands left to right, starting from value,
+// passing each result and its operator sequence to on_value, which returns whether to keep
+// searching.
+// Returns false once on_value has stopped the search.
+fn visit_forward<'op, N: CalibrationValue>(
+    value: &N,
+    operands: &[N],
+    operators: &[&'op dyn Operator<N>],
+    operator_sequence: &mut Vec<&'op dyn Operator<N>>,
+    on_value: &mut dyn FnMut(&N, &OperatorSequence<'op, N>) -> bool,
+) -> bool {
+    let Some((next_operand, remaining_operands)) = operands.split_first() else {
+        return on_value(value, operator_sequence);
+    };
+
+    for &operator in operators {
+        if let Some(next_value) = operator.apply(value, next_operand) {
+            operator_sequence.push(operator);
+            let keep_searching = visit_forward(
+                &next_value,
+                remaining_operands,
+                operators,
+                operator_sequence,
+                on_value,
+            );
+            operator_sequence.pop();
+            if !keep_searching {
+                return false;
+            }
         }
-        operator_sequence.pop();
     }
 
-    0
+    true
 }
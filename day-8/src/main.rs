@@ -1,5 +1,4 @@
 use std::{
-    cmp,
     collections::{HashMap, HashSet},
     error::Error,
     fs,
@@ -7,73 +6,190 @@ use std::{
 
 fn main() -> Result<(), Box<dyn Error>> {
     let map_source = fs::read_to_string("day-8/day8_input.txt")?;
-    let map_details = get_map_details(&map_source);
 
-    let antinodes = get_antinodes(&map_details);
-    println!("Number of unqiue antinode locations: {}", antinodes.len());
+    // Layouts made of several floors list each floor's map, separated by blank lines.
+    match split_map_layers(&map_source).as_slice() {
+        [] => return Err("Found no map in day-8/day8_input.txt.".into()),
+        [map_source] => report_antinodes(&get_map_details(map_source)),
+        layer_sources => report_antinodes(&get_layered_map_details(layer_sources)?),
+    }
+
+    Ok(())
+}
 
-    let antinodes_with_harmoics = get_antinodes_with_harmonics(&map_details);
+fn report_antinodes<const N: usize>(map_details: &MapDetails<N>) {
+    let antinodes = get_antinodes(map_details);
+    print_frequency_breakdown(map_details, &antinodes);
+    println!(
+        "Number of unqiue antinode locations: {}",
+        merge_antinodes(&antinodes).len()
+    );
+
+    let antinodes_with_harmoics = get_antinodes_with_harmonics(map_details, HarmonicsMode::Puzzle);
+    print_frequency_breakdown(map_details, &antinodes_with_harmoics);
     println!(
         "Number of unqiue antinode locations including effects of resonant harmonics: {}",
-        antinodes_with_harmoics.len()
+        merge_antinodes(&antinodes_with_harmoics).len()
     );
 
-    Ok(())
+    let lattice_antinodes = get_antinodes_with_harmonics(map_details, HarmonicsMode::Lattice);
+    println!(
+        "Number of unqiue antinode locations including every grid point in line: {}",
+        merge_antinodes(&lattice_antinodes).len()
+    );
+}
+
+// Prints the map with its antinodes, how many antinodes each frequency causes and the locations
+// where antinodes of different frequencies coincide.
+fn print_frequency_breakdown<const N: usize>(
+    map_details: &MapDetails<N>,
+    antinodes: &HashMap<char, HashSet<MapPoint<N>>>,
+) {
+    print!(
+        "{}",
+        render_antinode_map(map_details, &merge_antinodes(antinodes))
+    );
+
+    let mut frequencies: Vec<char> = antinodes.keys().copied().collect();
+    frequencies.sort_unstable();
+    println!("Frequency | Antennas | Antinodes");
+    for frequency in frequencies.iter() {
+        println!(
+            "{frequency:>9} | {:>8} | {:>9}",
+            map_details.antennas[frequency].len(),
+            antinodes[frequency].len()
+        );
+    }
+
+    let mut location_frequencies: HashMap<&MapPoint<N>, Vec<char>> = HashMap::new();
+    for frequency in frequencies {
+        for antinode in antinodes[&frequency].iter() {
+            location_frequencies
+                .entry(antinode)
+                .or_default()
+                .push(frequency);
+        }
+    }
+    let mut shared_locations: Vec<(&MapPoint<N>, Vec<char>)> = location_frequencies
+        .into_iter()
+        .filter(|(_, frequencies)| frequencies.len() > 1)
+        .collect();
+    shared_locations.sort_unstable_by_key(|(point, _)| point.coords);
+    println!(
+        "Antinode locations shared between frequencies: {}",
+        shared_locations.len()
+    );
+    for (point, frequencies) in shared_locations {
+        let frequencies: Vec<String> = frequencies.iter().map(char::to_string).collect();
+        println!("{:?}: {}", point.coords, frequencies.join(", "));
+    }
+}
+
+// Redraws the map with '#' marking antinodes that aren't covered by an antenna. Maps with more
+// than two dimensions are drawn one 2D slice at a time, separated by blank lines.
+fn render_antinode_map<const N: usize>(
+    map_details: &MapDetails<N>,
+    antinodes: &HashSet<&MapPoint<N>>,
+) -> String {
+    let mut antenna_glyphs = HashMap::new();
+    for (&frequency, antenna_points) in map_details.antennas.iter() {
+        for point in antenna_points {
+            antenna_glyphs.insert(point, frequency);
+        }
+    }
+
+    let mut render = String::new();
+    let mut point = MapPoint { coords: [0; N] };
+    loop {
+        render.push(match antenna_glyphs.get(&point) {
+            Some(&frequency) => frequency,
+            None if antinodes.contains(&point) => '#',
+            None => '.',
+        });
+
+        // Advance to the next point, ending rows and slices as their last coordinate wraps.
+        let mut axis = N;
+        loop {
+            if axis == 0 {
+                return render;
+            }
+            axis -= 1;
+            if point.coords[axis] < map_details.max_coords[axis] {
+                point.coords[axis] += 1;
+                break;
+            }
+            point.coords[axis] = 0;
+            if axis + 2 >= N {
+                render.push('\n');
+            }
+        }
+    }
+}
+
+fn merge_antinodes<const N: usize>(
+    antinodes: &HashMap<char, HashSet<MapPoint<N>>>,
+) -> HashSet<&MapPoint<N>> {
+    antinodes.values().flatten().collect()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HarmonicsMode {
+    // Antinodes repeat at whole multiples of the distance between the antennas.
+    Puzzle,
+    // Every grid point in line with the antennas is an antinode, including those between them.
+    Lattice,
+}
+
+// A point on an N dimensional antenna map. 2D maps use [row, col] coordinates and layered maps
+// [layer, row, col].
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct MapPoint {
-    row: i64,
-    col: i64,
+struct MapPoint<const N: usize> {
+    coords: [i64; N],
 }
 
-impl MapPoint {
+impl<const N: usize> MapPoint<N> {
+    // Returns the point as far past self as other is before it.
     fn next_antinode_from(&self, other: &Self) -> Self {
-        let distance = self.distance(other);
-
-        let antinode_row = match self.row.cmp(&other.row) {
-            cmp::Ordering::Less => self.row - distance.row,
-            cmp::Ordering::Greater => self.row + distance.row,
-            cmp::Ordering::Equal => self.row,
-        };
-        let antinode_col = match self.col.cmp(&other.col) {
-            cmp::Ordering::Less => self.col - distance.col,
-            cmp::Ordering::Greater => self.col + distance.col,
-            cmp::Ordering::Equal => self.col,
-        };
-
-        MapPoint {
-            row: antinode_row,
-            col: antinode_col,
+        self.translate(&other.offset_to(self))
+    }
+
+    fn offset_to(&self, other: &Self) -> [i64; N] {
+        let mut offset = other.coords;
+        for (offset_coord, coord) in offset.iter_mut().zip(self.coords) {
+            *offset_coord -= coord;
         }
+        offset
     }
 
-    fn distance(&self, other: &Self) -> Self {
-        MapPoint {
-            row: (self.row - other.row).abs(),
-            col: (self.col - other.col).abs(),
+    fn translate(&self, offset: &[i64; N]) -> Self {
+        let mut coords = self.coords;
+        for (coord, offset_coord) in coords.iter_mut().zip(offset) {
+            *coord += offset_coord;
         }
+
+        MapPoint { coords }
     }
 }
 
 #[derive(Debug, Clone)]
-struct MapDetails {
-    antennas: HashMap<char, Vec<MapPoint>>,
-    max_row: i64,
-    max_col: i64,
-}
-impl MapDetails {
-    fn is_valid_point(&self, point: &MapPoint) -> bool {
-        if point.row < 0 || point.row > self.max_row || point.col < 0 || point.col > self.max_col {
-            false
-        } else {
-            true
-        }
+struct MapDetails<const N: usize> {
+    antennas: HashMap<char, Vec<MapPoint<N>>>,
+    // Largest valid coordinate along each axis.
+    max_coords: [i64; N],
+}
+
+impl<const N: usize> MapDetails<N> {
+    fn is_valid_point(&self, point: &MapPoint<N>) -> bool {
+        point
+            .coords
+            .iter()
+            .zip(self.max_coords)
+            .all(|(&coord, max_coord)| (0..=max_coord).contains(&coord))
     }
 }
 
-fn get_map_details(input: &str) -> MapDetails {
-    let mut antennas: HashMap<char, Vec<MapPoint>> = HashMap::new();
+fn get_map_details(input: &str) -> MapDetails<2> {
+    let mut antennas: HashMap<char, Vec<MapPoint<2>>> = HashMap::new();
     let mut max_col = 0;
 
     let mut row = 0;
@@ -87,10 +203,11 @@ fn get_map_details(input: &str) -> MapDetails {
                 row += 1;
             }
             _ => {
+                let point = MapPoint { coords: [row, col] };
                 antennas
                     .entry(c)
-                    .and_modify(|frequency| frequency.push(MapPoint { row, col }))
-                    .or_insert(vec![MapPoint { row, col }]);
+                    .and_modify(|frequency| frequency.push(point.clone()))
+                    .or_insert(vec![point]);
                 col += 1;
             }
         }
@@ -100,94 +217,140 @@ fn get_map_details(input: &str) -> MapDetails {
 
     MapDetails {
         antennas,
-        max_row,
-        max_col,
+        max_coords: [max_row, max_col],
     }
 }
 
-fn get_antinodes(map_details: &MapDetails) -> HashSet<MapPoint> {
-    let mut antinodes = HashSet::new();
-    for antenna_points in map_details.antennas.values() {
-        let mut antenna_pairs = Vec::new();
-        create_antenna_pairs(antenna_points, &mut vec![], &mut antenna_pairs, 0);
-        for (point_1, point_2) in antenna_pairs {
-            let antinode_1 = point_1.next_antinode_from(&point_2);
+// Splits the input into the maps of each layer, which are separated by one or more blank lines.
+// Every line of a layer's map ends with a new line char.
+fn split_map_layers(input: &str) -> Vec<String> {
+    let mut layer_sources = Vec::new();
+    let mut layer_source = String::new();
+    for line in input.lines() {
+        if !line.trim().is_empty() {
+            layer_source.push_str(line);
+            layer_source.push('\n');
+        } else if !layer_source.is_empty() {
+            layer_sources.push(std::mem::take(&mut layer_source));
+        }
+    }
+    if !layer_source.is_empty() {
+        layer_sources.push(layer_source);
+    }
+
+    layer_sources
+}
+
+// Parses a stack of equally sized 2D maps, one per layer.
+fn get_layered_map_details(layer_sources: &[String]) -> Result<MapDetails<3>, String> {
+    let mut antennas: HashMap<char, Vec<MapPoint<3>>> = HashMap::new();
+    let mut max_coords = [0; 3];
+
+    for (layer, layer_source) in (0..).zip(layer_sources) {
+        let layer_details = get_map_details(layer_source);
+        if layer > 0 && layer_details.max_coords != max_coords[1..] {
+            return Err(format!(
+                "Layer {layer} is {} by {} plots, but the first layer is {} by {} plots.",
+                layer_details.max_coords[0] + 1,
+                layer_details.max_coords[1] + 1,
+                max_coords[1] + 1,
+                max_coords[2] + 1
+            ));
+        }
+        for (frequency, points) in layer_details.antennas {
+            antennas
+                .entry(frequency)
+                .or_default()
+                .extend(points.iter().map(|point| MapPoint {
+                    coords: [layer, point.coords[0], point.coords[1]],
+                }));
+        }
+        max_coords = [
+            layer,
+            layer_details.max_coords[0],
+            layer_details.max_coords[1],
+        ];
+    }
+
+    Ok(MapDetails {
+        antennas,
+        max_coords,
+    })
+}
+
+fn get_antinodes<const N: usize>(
+    map_details: &MapDetails<N>,
+) -> HashMap<char, HashSet<MapPoint<N>>> {
+    let mut antinodes_by_frequency = HashMap::new();
+    for (&frequency, antenna_points) in map_details.antennas.iter() {
+        let antinodes: &mut HashSet<MapPoint<N>> =
+            antinodes_by_frequency.entry(frequency).or_default();
+        for (point_1, point_2) in antenna_pairs(antenna_points) {
+            let antinode_1 = point_1.next_antinode_from(point_2);
             if map_details.is_valid_point(&antinode_1) {
                 antinodes.insert(antinode_1);
             }
 
-            let antinode_2 = point_2.next_antinode_from(&point_1);
+            let antinode_2 = point_2.next_antinode_from(point_1);
             if map_details.is_valid_point(&antinode_2) {
                 antinodes.insert(antinode_2);
             }
         }
     }
 
-    antinodes
-}
-
-fn get_antinodes_with_harmonics(map_details: &MapDetails) -> HashSet<MapPoint> {
-    let mut antinodes = HashSet::new();
-    for antenna_points in map_details.antennas.values() {
-        let mut antenna_pairs = Vec::new();
-        create_antenna_pairs(antenna_points, &mut vec![], &mut antenna_pairs, 0);
-        for (point_1, point_2) in antenna_pairs {
-            // Calculates antinodes down line from point_2 -> point_1
-            antinodes.insert(point_1.clone());
-            let mut start_point = point_1.clone();
-            let mut reference_point = point_2.clone();
-            loop {
-                let antinode = start_point.next_antinode_from(&reference_point);
-                match map_details.is_valid_point(&antinode) {
-                    true => {
-                        antinodes.insert(antinode.clone());
-                        reference_point = start_point;
-                        start_point = antinode;
-                    }
-                    false => {
-                        break;
-                    }
+    antinodes_by_frequency
+}
+
+fn get_antinodes_with_harmonics<const N: usize>(
+    map_details: &MapDetails<N>,
+    harmonics_mode: HarmonicsMode,
+) -> HashMap<char, HashSet<MapPoint<N>>> {
+    let mut antinodes_by_frequency = HashMap::new();
+    for (&frequency, antenna_points) in map_details.antennas.iter() {
+        let antinodes: &mut HashSet<MapPoint<N>> =
+            antinodes_by_frequency.entry(frequency).or_default();
+        for (point_1, point_2) in antenna_pairs(antenna_points) {
+            let mut step = point_1.offset_to(point_2);
+            if harmonics_mode == HarmonicsMode::Lattice {
+                let divisor = step.iter().fold(0, |divisor, &coord| gcd(divisor, coord));
+                for coord in step.iter_mut() {
+                    *coord /= divisor;
                 }
             }
+            let reverse_step = step.map(|coord| -coord);
 
-            // Calculates antinodes down line from point_1 -> point_2
-            antinodes.insert(point_2.clone());
-            start_point = point_2.clone();
-            reference_point = point_1.clone();
-            loop {
-                let antinode = start_point.next_antinode_from(&reference_point);
-                match map_details.is_valid_point(&antinode) {
-                    true => {
-                        antinodes.insert(antinode.clone());
-                        reference_point = start_point;
-                        start_point = antinode;
-                    }
-                    false => {
-                        break;
-                    }
+            // Walks the line through both antennas in each direction from point_1.
+            for step in [step, reverse_step] {
+                let mut antinode = point_1.clone();
+                while map_details.is_valid_point(&antinode) {
+                    let next_antinode = antinode.translate(&step);
+                    antinodes.insert(antinode);
+                    antinode = next_antinode;
                 }
             }
         }
     }
 
-    antinodes
+    antinodes_by_frequency
 }
 
-fn create_antenna_pairs<'antenna_points, 'antenna_pairs, 'current_pair>(
-    antenna_points: &'antenna_points Vec<MapPoint>,
-    current_pair: &'current_pair mut Vec<&'antenna_points MapPoint>,
-    antenna_pairs: &'antenna_pairs mut Vec<(&'antenna_points MapPoint, &'antenna_points MapPoint)>,
-    start_idx: usize,
-) {
-    if current_pair.len() == 2 {
-        let antenna_pair = (current_pair[0], current_pair[1]);
-        antenna_pairs.push(antenna_pair);
-        return;
-    }
+// Pairs every antenna with each antenna after it.
+fn antenna_pairs<const N: usize>(
+    antenna_points: &[MapPoint<N>],
+) -> impl Iterator<Item = (&MapPoint<N>, &MapPoint<N>)> {
+    antenna_points
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, point_1)| {
+            antenna_points[idx + 1..]
+                .iter()
+                .map(move |point_2| (point_1, point_2))
+        })
+}
 
-    for idx in start_idx..antenna_points.len() {
-        current_pair.push(&antenna_points[idx]);
-        create_antenna_pairs(antenna_points, current_pair, antenna_pairs, idx + 1);
-        current_pair.pop();
+fn gcd(a: i64, b: i64) -> i64 {
+    match b {
+        0 => a.abs(),
+        _ => gcd(b, a % b),
     }
 }
@@ -1,6 +1,122 @@
-use std::{error::Error, fs};
+use std::{cmp::Reverse, collections::BinaryHeap, env, error::Error, fs};
+
+// Disks longer than this are not printed in block notation.
+const MAX_BLOCK_NOTATION_LEN: usize = 100;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum CompactionStrategy {
+    FirstFit,
+    BestFit,
+    WorstFit,
+    // Repeats first-fit passes, letting files move into space freed by earlier passes, until no
+    // file can move any further left.
+    MultiPass,
+}
+
+impl CompactionStrategy {
+    fn name(&self) -> &'static str {
+        match self {
+            CompactionStrategy::FirstFit => "first-fit",
+            CompactionStrategy::BestFit => "best-fit",
+            CompactionStrategy::WorstFit => "worst-fit",
+            CompactionStrategy::MultiPass => "multi-pass",
+        }
+    }
+}
+
+struct CompactionReport {
+    checksum: usize,
+    moves: usize,
+    free_gaps: usize,
+    gap_blocks: usize,
+    largest_gap: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct FileSpan {
+    id: usize,
+    start: usize,
+    len: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct FreeSpan {
+    start: usize,
+    len: usize,
+}
+
+// Free spans indexed by their length, each length keeping a min-heap of span starts so the
+// leftmost span of a given length is always on top.
+struct FreeSpanIndex {
+    starts_by_len: Vec<BinaryHeap<Reverse<usize>>>,
+}
+
+impl FreeSpanIndex {
+    fn new(free_spans: &[FreeSpan]) -> Self {
+        let max_len = free_spans.iter().map(|span| span.len).max().unwrap_or(0);
+        let mut index = FreeSpanIndex {
+            starts_by_len: vec![BinaryHeap::new(); max_len + 1],
+        };
+        for span in free_spans {
+            index.insert(*span);
+        }
+
+        index
+    }
+
+    fn insert(&mut self, span: FreeSpan) {
+        if span.len > 0 {
+            self.starts_by_len[span.len].push(Reverse(span.start));
+        }
+    }
+
+    // Removes a free span that can hold `len` blocks and starts before `before`, returning its
+    // start. First-fit picks the leftmost such span, best-fit the shortest and worst-fit the
+    // longest, ties going to the leftmost. Any space left over in the span is put back.
+    fn take(&mut self, len: usize, before: usize, fit: CompactionStrategy) -> Option<usize> {
+        let mut candidates = (len.max(1)..self.starts_by_len.len()).filter_map(|span_len| {
+            let Reverse(start) = self.starts_by_len[span_len].peek()?;
+            (*start < before).then_some((*start, span_len))
+        });
+        let span_len = match fit {
+            CompactionStrategy::BestFit => candidates.next()?.1,
+            CompactionStrategy::WorstFit => candidates.next_back()?.1,
+            CompactionStrategy::FirstFit | CompactionStrategy::MultiPass => candidates.min()?.1,
+        };
+        let Reverse(start) = self.starts_by_len[span_len].pop()?;
+        self.insert(FreeSpan {
+            start: start + len,
+            len: span_len - len,
+        });
+
+        Some(start)
+    }
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let mut strategies = Vec::new();
+    let mut export_disk_maps = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => {
+                let name = args.next().ok_or("Expected a strategy after --strategy.")?;
+                strategies.push(parse_strategy(&name)?);
+            }
+            "--export" => export_disk_maps = true,
+            _ => return Err(format!("Unknown argument {arg}.").into()),
+        }
+    }
+    // Multi-pass repeats a whole pass per round and is slow on large disks, so it only runs when
+    // asked for.
+    if strategies.is_empty() {
+        strategies = vec![
+            CompactionStrategy::FirstFit,
+            CompactionStrategy::BestFit,
+            CompactionStrategy::WorstFit,
+        ];
+    }
+
     // NOTE: last char in disk_map is a new line char.
     let disk_map = fs::read_to_string("day-9/day9_input.txt")?;
 
@@ -10,12 +126,52 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     println!("Resulting filesystem checksum after v1 compaction: {checksum_v1}");
 
-    let mut block_layout_v2 = construct_block_layout(&disk_map[..disk_map.len() - 1]);
-    compact_files_v2(&mut block_layout_v2);
-    let checksum_v2 = calculate_checksum(&block_layout_v2);
+    let (mut file_spans, free_spans) = construct_span_layout(&disk_map[..disk_map.len() - 1]);
+    compact_files_v2(&mut file_spans, &free_spans);
+    let checksum_v2 = calculate_span_checksum(&file_spans);
 
     println!("Resulting filesystem checksum after v2 compaction: {checksum_v2}");
 
+    for strategy in strategies {
+        let (mut file_spans, free_spans) = construct_span_layout(&disk_map[..disk_map.len() - 1]);
+        let disk_len = calculate_disk_len(&file_spans, &free_spans);
+        let report = compact_with_strategy(&mut file_spans, &free_spans, strategy);
+        let fragmentation = match report.gap_blocks {
+            0 => 0.0,
+            gap_blocks => 1.0 - report.largest_gap as f64 / gap_blocks as f64,
+        };
+        println!(
+            "{}: checksum {}, {} file moves, {} free blocks left in {} gaps \
+             (external fragmentation {:.3})",
+            strategy.name(),
+            report.checksum,
+            report.moves,
+            report.gap_blocks,
+            report.free_gaps,
+            fragmentation
+        );
+
+        if let Some(block_notation) = render_block_notation(&file_spans, disk_len) {
+            println!("{block_notation}");
+        }
+
+        if !export_disk_maps {
+            continue;
+        }
+        // The dense map numbers files by position, so the original ID of every file entry is
+        // written alongside it, with '-' marking zero length filler files.
+        let (dense_disk_map, file_ids) = encode_dense_disk_map(&file_spans, disk_len);
+        let file_ids: Vec<String> = file_ids
+            .iter()
+            .map(|id| id.map_or("-".to_string(), |id| id.to_string()))
+            .collect();
+        let dense_path = format!("day-9/compacted_{}.txt", strategy.name());
+        let ids_path = format!("day-9/compacted_{}_ids.txt", strategy.name());
+        fs::write(&dense_path, dense_disk_map + "\n")?;
+        fs::write(&ids_path, file_ids.join(" ") + "\n")?;
+        println!("Wrote compacted disk map to {dense_path} and its file IDs to {ids_path}");
+    }
+
     Ok(())
 }
 
@@ -43,6 +199,34 @@ fn construct_block_layout(disk_map: &str) -> Vec<i64> {
     block_layout
 }
 
+fn construct_span_layout(disk_map: &str) -> (Vec<FileSpan>, Vec<FreeSpan>) {
+    let mut file_spans = Vec::new();
+    let mut free_spans: Vec<FreeSpan> = Vec::new();
+    let mut start = 0;
+
+    for (idx, c) in disk_map.chars().enumerate() {
+        let len = c
+            .to_digit(10)
+            .unwrap_or_else(|| panic!("Failed to parse {c} to a decimal digit."))
+            as usize;
+        match idx % 2 {
+            0 => file_spans.push(FileSpan {
+                id: idx / 2,
+                start,
+                len,
+            }),
+            // Free space on both sides of a zero length file forms a single run.
+            _ => match free_spans.last_mut() {
+                Some(span) if span.start + span.len == start => span.len += len,
+                _ => free_spans.push(FreeSpan { start, len }),
+            },
+        }
+        start += len;
+    }
+
+    (file_spans, free_spans)
+}
+
 fn compact_files_v1(block_layout: &mut [i64]) {
     let mut left_idx = 0;
     let mut right_idx = block_layout.len() - 1;
@@ -59,44 +243,154 @@ fn compact_files_v1(block_layout: &mut [i64]) {
     }
 }
 
-fn compact_files_v2(block_layout: &mut [i64]) {
-    let mut file_idx = block_layout.len() - 1;
+fn parse_strategy(name: &str) -> Result<CompactionStrategy, String> {
+    match name {
+        "first-fit" => Ok(CompactionStrategy::FirstFit),
+        "best-fit" => Ok(CompactionStrategy::BestFit),
+        "worst-fit" => Ok(CompactionStrategy::WorstFit),
+        "multi-pass" => Ok(CompactionStrategy::MultiPass),
+        _ => Err(format!(
+            "Unknown strategy {name}, expected first-fit, best-fit, worst-fit or multi-pass."
+        )),
+    }
+}
 
-    loop {
-        if block_layout[file_idx] != -1 {
-            // Calculate block size of current file.
-            let cur_file_id = block_layout[file_idx];
-            let mut file_block_size = 0;
-            while block_layout[file_idx] == cur_file_id && file_idx > 0 {
-                file_block_size += 1;
-                file_idx -= 1;
-            }
-            if file_idx == 0 {
+// Moves whole files, highest ID first, into the leftmost free span that fits them. Each file is
+// moved at most once and the space it leaves behind is never reused, since every file still to
+// be moved lies to its left.
+fn compact_files_v2(file_spans: &mut [FileSpan], free_spans: &[FreeSpan]) {
+    compact_files_once(file_spans, free_spans, CompactionStrategy::FirstFit);
+}
+
+// A single pass over the files, highest ID first, returning the number of files moved.
+fn compact_files_once(
+    file_spans: &mut [FileSpan],
+    free_spans: &[FreeSpan],
+    fit: CompactionStrategy,
+) -> usize {
+    let mut free_span_index = FreeSpanIndex::new(free_spans);
+    let mut moves = 0;
+    // Zero length files take up no blocks, so there is nothing to move.
+    for file in file_spans.iter_mut().rev().filter(|file| file.len > 0) {
+        if let Some(start) = free_span_index.take(file.len, file.start, fit) {
+            file.start = start;
+            moves += 1;
+        }
+    }
+
+    moves
+}
+
+fn compact_with_strategy(
+    file_spans: &mut [FileSpan],
+    free_spans: &[FreeSpan],
+    strategy: CompactionStrategy,
+) -> CompactionReport {
+    let mut moves = compact_files_once(file_spans, free_spans, strategy);
+    if strategy == CompactionStrategy::MultiPass {
+        loop {
+            let pass_moves = compact_files_once(file_spans, &find_free_gaps(file_spans), strategy);
+            if pass_moves == 0 {
                 break;
             }
-            file_idx += 1;
-            // Check if a large enough free space exists to its left.
-            let mut free_block_idx = 0;
-            let mut free_block_size = 0;
-            while free_block_idx <= file_idx {
-                if free_block_size == file_block_size {
-                    let free_block_start = free_block_idx - free_block_size as usize;
-                    for offset in 0..file_block_size {
-                        block_layout.swap(free_block_start + offset, file_idx + offset);
-                    }
-                    break;
-                }
-                match block_layout[free_block_idx] {
-                    -1 => free_block_size += 1,
-                    _ => free_block_size = 0,
-                }
-                free_block_idx += 1;
-            }
+            moves += pass_moves;
         }
-        if file_idx == 0 {
+    }
+
+    let free_gaps = find_free_gaps(file_spans);
+    CompactionReport {
+        checksum: calculate_span_checksum(file_spans),
+        moves,
+        free_gaps: free_gaps.len(),
+        gap_blocks: free_gaps.iter().map(|gap| gap.len).sum(),
+        largest_gap: free_gaps.iter().map(|gap| gap.len).max().unwrap_or(0),
+    }
+}
+
+// Free spans between the first and last file on the disk, in disk order.
+fn find_free_gaps(file_spans: &[FileSpan]) -> Vec<FreeSpan> {
+    let mut files_by_start: Vec<&FileSpan> =
+        file_spans.iter().filter(|file| file.len > 0).collect();
+    files_by_start.sort_unstable_by_key(|file| file.start);
+
+    files_by_start
+        .windows(2)
+        .filter(|files| files[0].start + files[0].len < files[1].start)
+        .map(|files| FreeSpan {
+            start: files[0].start + files[0].len,
+            len: files[1].start - files[0].start - files[0].len,
+        })
+        .collect()
+}
+
+fn calculate_disk_len(file_spans: &[FileSpan], free_spans: &[FreeSpan]) -> usize {
+    let file_ends = file_spans.iter().map(|file| file.start + file.len);
+    let free_ends = free_spans.iter().map(|span| span.start + span.len);
+    file_ends.chain(free_ends).max().unwrap_or(0)
+}
+
+// Renders the disk one character per block as in the puzzle, e.g. "0099811188827773336446555566..",
+// which only works for small disks with single digit file IDs.
+fn render_block_notation(file_spans: &[FileSpan], disk_len: usize) -> Option<String> {
+    if disk_len > MAX_BLOCK_NOTATION_LEN {
+        return None;
+    }
+
+    let mut blocks = vec!['.'; disk_len];
+    for file in file_spans {
+        let glyph = char::from_digit(u32::try_from(file.id).ok()?, 10)?;
+        blocks[file.start..file.start + file.len].fill(glyph);
+    }
+
+    Some(blocks.into_iter().collect())
+}
+
+// Encodes the disk back into a dense disk map, returning it with the original ID of each file
+// entry. Free runs longer than 9 blocks are split by zero length filler files, which have no ID.
+fn encode_dense_disk_map(file_spans: &[FileSpan], disk_len: usize) -> (String, Vec<Option<usize>>) {
+    let mut files_by_start: Vec<&FileSpan> =
+        file_spans.iter().filter(|file| file.len > 0).collect();
+    files_by_start.sort_unstable_by_key(|file| file.start);
+
+    let mut dense_disk_map = String::new();
+    let mut file_ids = Vec::new();
+    let mut disk_idx = 0;
+    // A disk map starts with a file, so a disk without any file blocks starts with a filler file.
+    if files_by_start.is_empty() && disk_len > 0 {
+        dense_disk_map.push('0');
+        file_ids.push(None);
+        push_free_run(&mut dense_disk_map, &mut file_ids, disk_len);
+        return (dense_disk_map, file_ids);
+    }
+    for (idx, file) in files_by_start.iter().enumerate() {
+        if idx > 0 {
+            push_free_run(&mut dense_disk_map, &mut file_ids, file.start - disk_idx);
+        } else if file.start > 0 {
+            dense_disk_map.push('0');
+            file_ids.push(None);
+            push_free_run(&mut dense_disk_map, &mut file_ids, file.start);
+        }
+        dense_disk_map.push(char::from_digit(file.len as u32, 10).unwrap());
+        file_ids.push(Some(file.id));
+        disk_idx = file.start + file.len;
+    }
+    if disk_len > disk_idx {
+        push_free_run(&mut dense_disk_map, &mut file_ids, disk_len - disk_idx);
+    }
+
+    (dense_disk_map, file_ids)
+}
+
+fn push_free_run(dense_disk_map: &mut String, file_ids: &mut Vec<Option<usize>>, mut len: usize) {
+    loop {
+        let run_len = len.min(9);
+        dense_disk_map.push(char::from_digit(run_len as u32, 10).unwrap());
+        len -= run_len;
+        if len == 0 {
             break;
         }
-        file_idx -= 1;
+        dense_disk_map.push('0');
+        file_ids.push(None);
     }
 }
 
@@ -110,3 +404,11 @@ fn calculate_checksum(block_layout: &[i64]) -> usize {
 
     checksum
 }
+
+fn calculate_span_checksum(file_spans: &[FileSpan]) -> usize {
+    file_spans
+        .iter()
+        .filter(|file| file.len > 0)
+        .map(|file| file.id * (file.start * file.len + file.len * (file.len - 1) / 2))
+        .sum()
+}